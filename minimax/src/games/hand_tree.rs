@@ -0,0 +1,91 @@
+use crate::Game;
+
+/// A game tree built by hand, where moves are the indices of the children. Every node has
+/// a value for the player to move in it, which is all that leaves have.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HandTree {
+    value: i32,
+    children: Vec<HandTree>,
+    // Whether the children are reached by noisy moves instead of ordinary ones
+    noisy: bool,
+}
+
+impl HandTree {
+    pub fn leaf(value: i32) -> HandTree {
+        HandTree {
+            value,
+            children: vec![],
+            noisy: false,
+        }
+    }
+
+    /// A node with the given children and the value 0.
+    pub fn node(children: Vec<HandTree>) -> HandTree {
+        HandTree {
+            value: 0,
+            children,
+            noisy: false,
+        }
+    }
+
+    pub fn with_value(self, value: i32) -> HandTree {
+        HandTree { value, ..self }
+    }
+
+    /// Makes the moves to the children noisy, so that they are only searched past the
+    /// depth limit.
+    pub fn noisy(self) -> HandTree {
+        HandTree { noisy: true, ..self }
+    }
+}
+
+impl Game for HandTree {
+    type Move = usize;
+
+    fn moves(&self) -> Vec<usize> {
+        if self.noisy {
+            vec![]
+        } else {
+            (0..self.children.len()).collect()
+        }
+    }
+
+    fn apply(&self, mv: usize) -> HandTree {
+        self.children[mv].clone()
+    }
+
+    fn is_terminal(&self) -> bool {
+        self.children.is_empty()
+    }
+
+    fn evaluate(&self) -> i32 {
+        self.value
+    }
+
+    fn noisy_moves(&self) -> Vec<usize> {
+        if self.noisy {
+            (0..self.children.len()).collect()
+        } else {
+            vec![]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_moves_lead_to_children() {
+        let tree = HandTree::node(vec![HandTree::leaf(1), HandTree::leaf(2)]).with_value(5);
+        assert_eq!(tree.moves(), vec![0, 1]);
+        assert_eq!(tree.apply(1), HandTree::leaf(2));
+        assert_eq!(tree.evaluate(), 5);
+        assert!(!tree.is_terminal());
+        assert!(tree.apply(0).is_terminal());
+
+        let tree = tree.noisy();
+        assert!(tree.moves().is_empty());
+        assert_eq!(tree.noisy_moves(), vec![0, 1]);
+    }
+}
@@ -0,0 +1,11 @@
+//! Small games with known values, to test searches with.
+
+mod hand_tree;
+mod nim;
+mod take_away;
+mod tic_tac_toe;
+
+pub use hand_tree::HandTree;
+pub use nim::Nim;
+pub use take_away::TakeAway;
+pub use tic_tac_toe::TicTacToe;
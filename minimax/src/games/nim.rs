@@ -0,0 +1,63 @@
+use crate::Game;
+
+/// Nim: the players take turns taking any number of objects from one heap, and whoever
+/// takes the last object wins.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Nim {
+    heaps: Vec<u32>,
+}
+
+impl Nim {
+    /// Value of a won game.
+    pub const WIN: i32 = 1;
+
+    pub fn new(heaps: Vec<u32>) -> Nim {
+        Nim { heaps }
+    }
+}
+
+impl Game for Nim {
+    /// The heap to take from, and how many objects to take.
+    type Move = (usize, u32);
+
+    fn moves(&self) -> Vec<(usize, u32)> {
+        self.heaps
+            .iter()
+            .enumerate()
+            .flat_map(|(heap, &size)| (1..=size).map(move |taken| (heap, taken)))
+            .collect()
+    }
+
+    fn apply(&self, (heap, taken): (usize, u32)) -> Nim {
+        let mut heaps = self.heaps.clone();
+        heaps[heap] -= taken;
+        Nim { heaps }
+    }
+
+    fn is_terminal(&self) -> bool {
+        self.heaps.iter().all(|&size| size == 0)
+    }
+
+    fn evaluate(&self) -> i32 {
+        // The opponent took the last object
+        if self.is_terminal() {
+            -Nim::WIN
+        } else {
+            0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_taking_the_last_object_wins() {
+        let game = Nim::new(vec![0, 2]);
+        assert_eq!(game.moves(), vec![(1, 1), (1, 2)]);
+        let game = game.apply((1, 2));
+        assert!(game.is_terminal());
+        assert_eq!(game.evaluate(), -Nim::WIN);
+    }
+}
@@ -0,0 +1,72 @@
+use crate::multiplayer::MultiPlayerGame;
+
+/// A take-away game for any number of players: they take turns taking one or two tokens
+/// from a pile, and whoever takes the last token wins.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TakeAway {
+    pile: u32,
+    players: usize,
+    to_move: usize,
+}
+
+impl TakeAway {
+    /// Value of a won game for the winner, the others get 0.
+    pub const WIN: i32 = 1;
+
+    pub fn new(pile: u32, players: usize) -> TakeAway {
+        TakeAway {
+            pile,
+            players,
+            to_move: 0,
+        }
+    }
+}
+
+impl MultiPlayerGame for TakeAway {
+    /// The number of tokens to take.
+    type Move = u32;
+
+    fn players(&self) -> usize {
+        self.players
+    }
+
+    fn to_move(&self) -> usize {
+        self.to_move
+    }
+
+    fn moves(&self) -> Vec<u32> {
+        (1..=self.pile.min(2)).collect()
+    }
+
+    fn apply(&self, taken: u32) -> TakeAway {
+        TakeAway {
+            pile: self.pile - taken,
+            players: self.players,
+            to_move: (self.to_move + 1) % self.players,
+        }
+    }
+
+    fn evaluate(&self) -> Vec<i32> {
+        let mut values = vec![0; self.players];
+        if self.pile == 0 {
+            // The previous player took the last token
+            values[(self.to_move + self.players - 1) % self.players] = TakeAway::WIN;
+        }
+        values
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_taking_the_last_token_wins() {
+        let game = TakeAway::new(2, 3).apply(1);
+        assert_eq!(game.to_move(), 1);
+        assert_eq!(game.evaluate(), vec![0, 0, 0]);
+        let game = game.apply(1);
+        assert!(game.is_terminal());
+        assert_eq!(game.evaluate(), vec![0, 1, 0]);
+    }
+}
@@ -0,0 +1,104 @@
+use crate::Game;
+
+const LINES: [[usize; 3]; 8] = [
+    [0, 1, 2],
+    [3, 4, 5],
+    [6, 7, 8],
+    [0, 3, 6],
+    [1, 4, 7],
+    [2, 5, 8],
+    [0, 4, 8],
+    [2, 4, 6],
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Mark {
+    X,
+    O,
+}
+
+/// Tic-tac-toe, where X moves first and moves are the squares 0 to 8, row by row.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TicTacToe {
+    squares: [Option<Mark>; 9],
+}
+
+impl TicTacToe {
+    /// Value of a won game.
+    pub const WIN: i32 = 1;
+
+    /// The position with the given rows of `X`, `O` and `.` for an empty square.
+    pub fn from_rows(rows: [&str; 3]) -> TicTacToe {
+        let mut squares = [None; 9];
+        for (square, c) in rows.concat().chars().enumerate() {
+            squares[square] = match c {
+                'X' => Some(Mark::X),
+                'O' => Some(Mark::O),
+                _ => None,
+            };
+        }
+        TicTacToe { squares }
+    }
+
+    fn to_move(&self) -> Mark {
+        let marks = self.squares.iter().flatten().count();
+        if marks.is_multiple_of(2) {
+            Mark::X
+        } else {
+            Mark::O
+        }
+    }
+
+    fn winner(&self) -> Option<Mark> {
+        LINES.iter().find_map(|line| {
+            let mark = self.squares[line[0]]?;
+            line.iter().all(|&square| self.squares[square] == Some(mark)).then_some(mark)
+        })
+    }
+}
+
+impl Game for TicTacToe {
+    type Move = usize;
+
+    fn moves(&self) -> Vec<usize> {
+        if self.winner().is_some() {
+            return vec![];
+        }
+        (0..9).filter(|&square| self.squares[square].is_none()).collect()
+    }
+
+    fn apply(&self, square: usize) -> TicTacToe {
+        let mut squares = self.squares;
+        squares[square] = Some(self.to_move());
+        TicTacToe { squares }
+    }
+
+    fn evaluate(&self) -> i32 {
+        match self.winner() {
+            Some(mark) if mark == self.to_move() => TicTacToe::WIN,
+            Some(_) => -TicTacToe::WIN,
+            None => 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_completed_line_ends_the_game() {
+        let game = TicTacToe::from_rows(["XX.", "OO.", "..."]);
+        assert!(!game.is_terminal());
+        let game = game.apply(2);
+        assert!(game.is_terminal());
+        assert_eq!(game.evaluate(), -TicTacToe::WIN);
+    }
+
+    #[test]
+    fn test_full_board_is_a_draw() {
+        let game = TicTacToe::from_rows(["XOX", "XOO", "OXX"]);
+        assert!(game.is_terminal());
+        assert_eq!(game.evaluate(), 0);
+    }
+}
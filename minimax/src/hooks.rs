@@ -0,0 +1,388 @@
+//! Alpha-beta search with hooks for what a game engine adds around it: a transposition
+//! table, move ordering, pruning, extensions and reductions, and whatever it keeps along the
+//! searched line. Without any of that, it is [`alpha_beta`](crate::alpha_beta).
+//!
+//! For every position, the search calls the hooks in this order, stopping at the first one
+//! that decides the value of the position:
+//!
+//! 1. [`Observer::enter`], which may abandon the search.
+//! 2. [`Hooks::probe`], for a value known without searching, as from a transposition table.
+//! 3. [`Hooks::leaf`] at the depth limit.
+//! 4. [`Hooks::prune`], for a value decided before any move is searched.
+//! 5. [`Hooks::moves`], and [`Hooks::terminal`] if there are none.
+//! 6. For every move, [`Hooks::visit`], then the search of the position it leads to, then
+//!    [`Hooks::leave`]. A move that reaches beta is passed to [`Hooks::refuted`].
+//! 7. [`Hooks::store`] with the value found.
+//!
+//! The first move gets the full window, the others only a null window at alpha, and are
+//! searched again with the full window if they beat alpha, which is principal variation
+//! search.
+
+use crate::{quiesce_entered, Game, Observer, INFINITY};
+
+/// How deep a position is searched, in moves from the depth limit, and the window of values
+/// it is searched with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Window {
+    pub depth: u32,
+    pub alpha: i32,
+    pub beta: i32,
+}
+
+/// What probing a position found.
+pub enum Probe<N> {
+    /// The value of the position, which needs no search.
+    Value(i32),
+    /// What the hooks keep about the position while it is searched.
+    Search(N),
+}
+
+/// What pruning a position before its moves are searched decided.
+pub enum Prune {
+    /// The value of the position, without searching its moves.
+    Value(i32),
+    /// Nothing, the moves are searched.
+    Search,
+    /// Abandons the search.
+    Abandon,
+}
+
+/// How a move is searched.
+pub enum Visit {
+    /// Searched `extension` moves deeper than the depth left, and first `reduction` moves
+    /// shallower than that with a null window, which is only done for the moves after the
+    /// first. A reduced move that beats alpha is searched again without the reduction.
+    Search { extension: u32, reduction: u32 },
+    /// Not searched, as its value is known to be no more than `bound`.
+    Skip { bound: i32 },
+}
+
+/// The hooks of a search. Every one of them does nothing by default, which leaves plain
+/// alpha-beta search with quiescence at the depth limit.
+pub trait Hooks<G: Game>: Observer + Sized {
+    /// What the hooks keep about a position while its moves are searched.
+    type Node: Default;
+
+    /// Looks the position up, before anything else is done with it.
+    fn probe(&mut self, _game: &G, _window: Window) -> Probe<Self::Node> {
+        Probe::Search(Self::Node::default())
+    }
+
+    /// Value of a position at the depth limit, `ply` moves after the position the search
+    /// started from, or `None` to abandon the search. By default it is the quiescence search
+    /// of the position, which was already entered.
+    fn leaf(&mut self, game: &G, _node: &mut Self::Node, window: Window, ply: usize) -> Option<i32> {
+        quiesce_entered(game, window.alpha, window.beta, ply, self)
+    }
+
+    /// Decides the value of the position before its moves are searched, if it can.
+    fn prune(&mut self, _game: &G, _node: &mut Self::Node, _window: Window) -> Prune {
+        Prune::Search
+    }
+
+    /// The moves of the position in the order they are searched. By default those of the
+    /// game, and none where it is over.
+    fn moves(&mut self, game: &G, _node: &mut Self::Node) -> Vec<G::Move> {
+        if game.is_terminal() {
+            vec![]
+        } else {
+            game.moves()
+        }
+    }
+
+    /// Value of a position without moves. By default its evaluation.
+    fn terminal(&mut self, game: &G, _node: &mut Self::Node) -> i32 {
+        game.evaluate()
+    }
+
+    /// Decides how `mv` to `child` is searched, after `searched` moves of the position were,
+    /// with alpha in the window raised by them. A searched move is left with
+    /// [`Hooks::leave`], a skipped one is not.
+    fn visit(
+        &mut self,
+        _game: &G,
+        _node: &mut Self::Node,
+        _mv: G::Move,
+        _child: &G,
+        _window: Window,
+        _searched: usize,
+    ) -> Visit {
+        Visit::Search {
+            extension: 0,
+            reduction: 0,
+        }
+    }
+
+    /// Called after a visited move was searched, even if the search was abandoned.
+    fn leave(&mut self, _game: &G, _node: &mut Self::Node, _mv: G::Move, _extension: u32) {}
+
+    /// Called whenever a move is searched again with a wider window or without its reduction.
+    fn re_searched(&mut self) {}
+
+    /// Called when `mv` reaches beta, so that the remaining moves are not searched.
+    fn refuted(&mut self, _game: &G, _node: &mut Self::Node, _mv: G::Move, _depth: u32) {}
+
+    /// Called with the value of a position whose moves were searched, the best of them if
+    /// one beat all others, and the window it was searched with.
+    fn store(&mut self, _game: &G, _node: &mut Self::Node, _window: Window, _value: i32, _best_move: Option<G::Move>) {}
+}
+
+/// Hooks that do nothing but watch with an observer.
+pub(crate) struct Plain<'a, O>(pub &'a mut O);
+
+impl<O: Observer> Observer for Plain<'_, O> {
+    fn enter(&mut self, ply: usize) -> bool {
+        self.0.enter(ply)
+    }
+
+    fn evaluated(&mut self) {
+        self.0.evaluated();
+    }
+
+    fn cutoff(&mut self) {
+        self.0.cutoff();
+    }
+}
+
+impl<G: Game, O: Observer> Hooks<G> for Plain<'_, O> {
+    type Node = ();
+}
+
+/// Value of the position searched to `depth` moves with the hooks, within the window from
+/// `alpha` to `beta`, with the same bounds as [`alpha_beta`](crate::alpha_beta). Returns
+/// `None` if the hooks abandoned the search.
+pub fn search<G: Game, H: Hooks<G>>(game: &G, depth: u32, alpha: i32, beta: i32, hooks: &mut H) -> Option<i32> {
+    search_node(game, depth, alpha, beta, 0, hooks)
+}
+
+fn search_node<G: Game, H: Hooks<G>>(
+    game: &G,
+    depth: u32,
+    mut alpha: i32,
+    beta: i32,
+    ply: usize,
+    hooks: &mut H,
+) -> Option<i32> {
+    if !hooks.enter(ply) {
+        return None;
+    }
+    let window = Window { depth, alpha, beta };
+    let mut node = match hooks.probe(game, window) {
+        Probe::Value(value) => return Some(value),
+        Probe::Search(node) => node,
+    };
+    if depth == 0 {
+        return hooks.leaf(game, &mut node, window, ply);
+    }
+    match hooks.prune(game, &mut node, window) {
+        Prune::Value(value) => return Some(value),
+        Prune::Search => {}
+        Prune::Abandon => return None,
+    }
+    let moves = hooks.moves(game, &mut node);
+    if moves.is_empty() {
+        hooks.evaluated();
+        return Some(hooks.terminal(game, &mut node));
+    }
+
+    let mut searched = 0;
+    let mut best = -INFINITY;
+    let mut best_move = None;
+    for mv in moves {
+        let child = game.apply(mv);
+        let raised = Window { alpha, ..window };
+        let (extension, reduction) = match hooks.visit(game, &mut node, mv, &child, raised, searched) {
+            Visit::Search { extension, reduction } => (extension, reduction),
+            Visit::Skip { bound } => {
+                best = best.max(bound);
+                continue;
+            }
+        };
+        let child_depth = depth - 1 + extension;
+        let value = if searched == 0 {
+            search_node(&child, child_depth, -beta, -alpha, ply + 1, hooks).map(|value| -value)
+        } else {
+            null_window(&child, child_depth, reduction, alpha, beta, ply + 1, hooks)
+        };
+        hooks.leave(game, &mut node, mv, extension);
+        let value = value?;
+        searched += 1;
+
+        if value > best {
+            best = value;
+            best_move = Some(mv);
+        }
+        alpha = alpha.max(value);
+        if alpha >= beta {
+            hooks.cutoff();
+            hooks.refuted(game, &mut node, mv, depth);
+            break;
+        }
+    }
+    hooks.store(game, &mut node, window, best, best_move);
+    Some(best)
+}
+
+/// Value of a move after the first one for the player who makes it: shown to be no more than
+/// alpha with a null window, and searched again if it is not.
+fn null_window<G: Game, H: Hooks<G>>(
+    child: &G,
+    depth: u32,
+    reduction: u32,
+    alpha: i32,
+    beta: i32,
+    ply: usize,
+    hooks: &mut H,
+) -> Option<i32> {
+    let mut value = -search_node(child, depth.saturating_sub(reduction), -alpha - 1, -alpha, ply, hooks)?;
+    if value > alpha && reduction > 0 {
+        hooks.re_searched();
+        value = -search_node(child, depth, -alpha - 1, -alpha, ply, hooks)?;
+    }
+    if value > alpha && value < beta {
+        hooks.re_searched();
+        value = -search_node(child, depth, -beta, -alpha, ply, hooks)?;
+    }
+    Some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::games::HandTree;
+    use crate::{alpha_beta, negamax};
+
+    fn tree() -> HandTree {
+        HandTree::node(vec![
+            HandTree::node(vec![HandTree::leaf(3), HandTree::leaf(12)]),
+            HandTree::node(vec![HandTree::leaf(2), HandTree::leaf(4)]),
+            HandTree::node(vec![HandTree::leaf(14), HandTree::leaf(2)]),
+        ])
+    }
+
+    // Searches the moves in reverse, extends the moves of the root and skips all but the first
+    // move of a position, as told, and records what it was asked
+    #[derive(Default)]
+    struct Recorder {
+        reverse: bool,
+        extend: bool,
+        skip: bool,
+        root_moves: Vec<usize>,
+        stored: Vec<(u32, i32, Option<usize>)>,
+        refuted: usize,
+        depth: usize,
+    }
+
+    impl Observer for Recorder {}
+
+    impl Hooks<HandTree> for Recorder {
+        type Node = ();
+
+        fn moves(&mut self, game: &HandTree, _node: &mut ()) -> Vec<usize> {
+            let mut moves = game.moves();
+            if self.reverse {
+                moves.reverse();
+            }
+            moves
+        }
+
+        fn visit(
+            &mut self,
+            _game: &HandTree,
+            _node: &mut (),
+            mv: usize,
+            _child: &HandTree,
+            _window: Window,
+            searched: usize,
+        ) -> Visit {
+            if self.depth == 0 {
+                self.root_moves.push(mv);
+            }
+            if self.skip && searched > 0 {
+                return Visit::Skip { bound: -100 };
+            }
+            self.depth += 1;
+            Visit::Search {
+                extension: (self.extend && self.depth == 1) as u32,
+                reduction: 0,
+            }
+        }
+
+        fn leave(&mut self, _game: &HandTree, _node: &mut (), _mv: usize, _extension: u32) {
+            self.depth -= 1;
+        }
+
+        fn refuted(&mut self, _game: &HandTree, _node: &mut (), _mv: usize, _depth: u32) {
+            self.refuted += 1;
+        }
+
+        fn store(&mut self, _game: &HandTree, _node: &mut (), window: Window, value: i32, best_move: Option<usize>) {
+            self.stored.push((window.depth, value, best_move));
+        }
+    }
+
+    #[test]
+    fn test_without_hooks_it_is_alpha_beta() {
+        let mut recorder = Recorder::default();
+        assert_eq!(search(&tree(), 2, -INFINITY, INFINITY, &mut recorder), Some(3));
+        assert_eq!(alpha_beta(&tree(), 2, -INFINITY, INFINITY, &mut ()), Some(3));
+        assert_eq!(recorder.root_moves, [0, 1, 2]);
+        // The second and third subtrees are refuted by their first leaf
+        assert_eq!(recorder.refuted, 2);
+        assert_eq!(recorder.stored.last(), Some(&(2, 3, Some(0))));
+    }
+
+    #[test]
+    fn test_moves_are_searched_in_the_order_of_the_hooks() {
+        let mut recorder = Recorder {
+            reverse: true,
+            ..Recorder::default()
+        };
+        assert_eq!(search(&tree(), 2, -INFINITY, INFINITY, &mut recorder), Some(3));
+        assert_eq!(recorder.root_moves, [2, 1, 0]);
+        assert_eq!(recorder.stored.last(), Some(&(2, 3, Some(0))));
+    }
+
+    #[test]
+    fn test_extended_moves_are_searched_deeper() {
+        // Searched a move deeper, the leaves are valued for the root player again
+        let mut recorder = Recorder {
+            extend: true,
+            ..Recorder::default()
+        };
+        let value = search(&tree(), 1, -INFINITY, INFINITY, &mut recorder);
+        assert_eq!(value, Some(negamax(&tree(), 2)));
+    }
+
+    #[test]
+    fn test_skipped_moves_count_with_their_bound() {
+        let mut recorder = Recorder {
+            skip: true,
+            ..Recorder::default()
+        };
+        // Only the first move of every position is searched
+        assert_eq!(search(&tree(), 2, -INFINITY, INFINITY, &mut recorder), Some(3));
+        assert_eq!(recorder.root_moves, [0, 1, 2]);
+        // The skipped move may be better than the searched one, by as much as its bound
+        let tree = HandTree::node(vec![HandTree::leaf(150), HandTree::leaf(0)]);
+        assert_eq!(search(&tree, 1, -INFINITY, INFINITY, &mut recorder), Some(-100));
+    }
+
+    #[test]
+    fn test_probed_values_end_the_search() {
+        struct Table;
+        impl Observer for Table {}
+        impl Hooks<HandTree> for Table {
+            type Node = ();
+
+            fn probe(&mut self, _game: &HandTree, window: Window) -> Probe<()> {
+                if window.depth == 1 {
+                    Probe::Value(-7)
+                } else {
+                    Probe::Search(())
+                }
+            }
+        }
+        assert_eq!(search(&tree(), 2, -INFINITY, INFINITY, &mut Table), Some(7));
+    }
+}
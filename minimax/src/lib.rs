@@ -0,0 +1,307 @@
+//! Minimax search that knows nothing about the game it searches.
+//!
+//! A game only has to implement [`Game`]: its moves, what a move leads to, when the game is
+//! over and how good a position is for the player to move. The search is negamax, so all
+//! values are from the point of view of the player to move. Games with noisy moves, like
+//! captures in chess, can have them searched past the depth limit until the position is
+//! quiet.
+//!
+//! Engines that add a transposition table, move ordering or pruning to the search plug them
+//! in through [`hooks`].
+//!
+//! Games of more than two players are searched with [`multiplayer`].
+//!
+//! Small stub games to test searches with are in [`games`].
+
+pub mod games;
+pub mod hooks;
+pub mod multiplayer;
+
+use hooks::Plain;
+
+/// Bound of all values, no position is worth more than this or less than its negation.
+pub const INFINITY: i32 = i32::MAX;
+
+/// A two player game, seen from a position in it.
+pub trait Game: Sized {
+    type Move: Copy;
+
+    /// The moves of the player to move, searched in the order they are returned.
+    fn moves(&self) -> Vec<Self::Move>;
+
+    fn apply(&self, mv: Self::Move) -> Self;
+
+    /// Whether the game is over. By default it is when there are no moves left.
+    fn is_terminal(&self) -> bool {
+        self.moves().is_empty()
+    }
+
+    /// Value of the position for the player to move. In a terminal position that is the
+    /// result of the game.
+    fn evaluate(&self) -> i32;
+
+    /// Moves that are searched past the depth limit, until there are none left. Terminal
+    /// positions have none. There are none by default.
+    fn noisy_moves(&self) -> Vec<Self::Move> {
+        vec![]
+    }
+
+    /// Whether the player to move may decline the noisy moves and take the evaluation of the
+    /// position instead, as a chess player may not while in check.
+    fn can_stand_pat(&self) -> bool {
+        true
+    }
+}
+
+/// Watches a search, and can abandon it.
+pub trait Observer {
+    /// Called when the search enters a position, `ply` moves after the position it started
+    /// from. Returning false abandons the search.
+    fn enter(&mut self, _ply: usize) -> bool {
+        true
+    }
+
+    /// Called whenever a position is evaluated.
+    fn evaluated(&mut self) {}
+
+    /// Called whenever the remaining moves of a position are pruned.
+    fn cutoff(&mut self) {}
+}
+
+/// Watches nothing, and never abandons a search.
+impl Observer for () {}
+
+/// Value of the position searched to `depth` moves without any pruning, followed by all
+/// noisy moves. Slow, but simple enough to check faster searches against.
+pub fn negamax<G: Game>(game: &G, depth: u32) -> i32 {
+    if depth == 0 {
+        return quiet_negamax(game);
+    }
+    if game.is_terminal() {
+        return game.evaluate();
+    }
+    game.moves()
+        .into_iter()
+        .map(|mv| -negamax(&game.apply(mv), depth - 1))
+        .max()
+        .unwrap_or_else(|| game.evaluate())
+}
+
+fn quiet_negamax<G: Game>(game: &G) -> i32 {
+    let stand_pat = game.can_stand_pat().then(|| game.evaluate());
+    let best = game
+        .noisy_moves()
+        .into_iter()
+        .map(|mv| -quiet_negamax(&game.apply(mv)))
+        .max();
+    match (stand_pat, best) {
+        (Some(stand_pat), Some(best)) => stand_pat.max(best),
+        (Some(value), None) | (None, Some(value)) => value,
+        (None, None) => game.evaluate(),
+    }
+}
+
+/// Value of the position searched to `depth` moves, followed by the noisy moves, within the
+/// window from `alpha` to `beta`. A value at or below `alpha` is only an upper bound of the
+/// real one, and a value at or above `beta` only a lower bound. Returns `None` if the
+/// observer abandoned the search. This is [`hooks::search`] without hooks.
+pub fn alpha_beta<G: Game>(
+    game: &G,
+    depth: u32,
+    alpha: i32,
+    beta: i32,
+    observer: &mut impl Observer,
+) -> Option<i32> {
+    hooks::search(game, depth, alpha, beta, &mut Plain(observer))
+}
+
+/// Value of the position with only the noisy moves searched, within the window from `alpha`
+/// to `beta`. Returns `None` if the observer abandoned the search.
+pub fn quiescence<G: Game>(game: &G, alpha: i32, beta: i32, observer: &mut impl Observer) -> Option<i32> {
+    quiesce(game, alpha, beta, 0, observer)
+}
+
+/// The best move of the position searched to `depth` moves, with its value, or `None` if
+/// the game is over.
+pub fn best_move<G: Game>(game: &G, depth: u32) -> Option<(G::Move, i32)> {
+    if game.is_terminal() {
+        return None;
+    }
+    let mut best: Option<(G::Move, i32)> = None;
+    for mv in game.moves() {
+        let alpha = best.map_or(-INFINITY, |(_, value)| value);
+        let value = -alpha_beta(&game.apply(mv), depth.saturating_sub(1), -INFINITY, -alpha, &mut ())?;
+        if best.is_none_or(|(_, best_value)| value > best_value) {
+            best = Some((mv, value));
+        }
+    }
+    best
+}
+
+fn quiesce<G: Game>(game: &G, alpha: i32, beta: i32, ply: usize, observer: &mut impl Observer) -> Option<i32> {
+    if !observer.enter(ply) {
+        return None;
+    }
+    quiesce_entered(game, alpha, beta, ply, observer)
+}
+
+/// Quiescence search of a position the observer already entered.
+fn quiesce_entered<G: Game>(
+    game: &G,
+    mut alpha: i32,
+    beta: i32,
+    ply: usize,
+    observer: &mut impl Observer,
+) -> Option<i32> {
+    let mut best = -INFINITY;
+    let can_stand_pat = game.can_stand_pat();
+    if can_stand_pat {
+        observer.evaluated();
+        let stand_pat = game.evaluate();
+        if stand_pat >= beta {
+            return Some(stand_pat);
+        }
+        alpha = alpha.max(stand_pat);
+        best = stand_pat;
+    }
+
+    let moves = game.noisy_moves();
+    if moves.is_empty() && !can_stand_pat {
+        observer.evaluated();
+        return Some(game.evaluate());
+    }
+    for mv in moves {
+        let value = -quiesce(&game.apply(mv), -beta, -alpha, ply + 1, observer)?;
+        best = best.max(value);
+        alpha = alpha.max(value);
+        if alpha >= beta {
+            observer.cutoff();
+            break;
+        }
+    }
+    Some(best)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::games::{HandTree, Nim, TicTacToe};
+
+    // A textbook example, where the middle and right subtrees are cut off early
+    fn pruned_tree() -> HandTree {
+        HandTree::node(vec![
+            HandTree::node(vec![HandTree::leaf(3), HandTree::leaf(12), HandTree::leaf(8)]),
+            HandTree::node(vec![HandTree::leaf(2), HandTree::leaf(4), HandTree::leaf(6)]),
+            HandTree::node(vec![HandTree::leaf(14), HandTree::leaf(5), HandTree::leaf(2)]),
+        ])
+    }
+
+    #[derive(Default)]
+    struct Counter {
+        nodes: usize,
+        cutoffs: usize,
+        max_nodes: Option<usize>,
+    }
+
+    impl Observer for Counter {
+        fn enter(&mut self, _ply: usize) -> bool {
+            self.nodes += 1;
+            self.max_nodes.is_none_or(|max_nodes| self.nodes <= max_nodes)
+        }
+
+        fn cutoff(&mut self) {
+            self.cutoffs += 1;
+        }
+    }
+
+    #[test]
+    fn test_values_of_hand_built_tree() {
+        // At depth 2 the root player is to move again, so the leaves are valued for them and
+        // the opponent picks the smallest leaf of each subtree
+        let tree = pruned_tree();
+        assert_eq!(negamax(&tree, 2), 3);
+        assert_eq!(alpha_beta(&tree, 2, -INFINITY, INFINITY, &mut ()), Some(3));
+        assert_eq!(best_move(&tree, 2), Some((0, 3)));
+    }
+
+    #[test]
+    fn test_alpha_beta_prunes_refuted_moves() {
+        let mut counter = Counter::default();
+        alpha_beta(&pruned_tree(), 2, -INFINITY, INFINITY, &mut counter);
+        assert_eq!(counter.cutoffs, 2);
+        // The root, its three children and seven of the nine leaves
+        assert_eq!(counter.nodes, 11);
+    }
+
+    #[test]
+    fn test_bounds_outside_window() {
+        let tree = pruned_tree();
+        let fail_high = alpha_beta(&tree, 2, -INFINITY, 1, &mut ()).unwrap();
+        assert!((1..=3).contains(&fail_high));
+        let fail_low = alpha_beta(&tree, 2, 5, INFINITY, &mut ()).unwrap();
+        assert!((3..=5).contains(&fail_low));
+    }
+
+    #[test]
+    fn test_tic_tac_toe_is_a_draw() {
+        let game = TicTacToe::default();
+        assert_eq!(negamax(&game, 9), 0);
+        assert_eq!(alpha_beta(&game, 9, -INFINITY, INFINITY, &mut ()), Some(0));
+    }
+
+    #[test]
+    fn test_tic_tac_toe_win_is_found() {
+        // X to move, with a win on the top row
+        let game = TicTacToe::from_rows(["XX.", "OO.", "..."]);
+        assert_eq!(best_move(&game, 9), Some((2, TicTacToe::WIN)));
+        // O to move can block only one of the two threats
+        let game = TicTacToe::from_rows(["X.X", ".O.", "O.X"]);
+        assert_eq!(alpha_beta(&game, 9, -INFINITY, INFINITY, &mut ()), Some(-TicTacToe::WIN));
+    }
+
+    #[test]
+    fn test_nim_values_match_theory() {
+        // The player to move loses exactly when the heaps xor to zero
+        for heaps in [vec![1, 2, 3], vec![1, 2, 4], vec![2, 2], vec![3, 3], vec![5]] {
+            let xor = heaps.iter().fold(0, |xor, heap| xor ^ heap);
+            let game = Nim::new(heaps.clone());
+            let expected = if xor == 0 { -Nim::WIN } else { Nim::WIN };
+            assert_eq!(negamax(&game, 20), expected, "heaps {:?}", heaps);
+            assert_eq!(alpha_beta(&game, 20, -INFINITY, INFINITY, &mut ()), Some(expected));
+        }
+    }
+
+    #[test]
+    fn test_alpha_beta_agrees_with_negamax_at_every_depth() {
+        let game = TicTacToe::from_rows(["X..", ".O.", "..."]);
+        for depth in 0..8 {
+            assert_eq!(
+                alpha_beta(&game, depth, -INFINITY, INFINITY, &mut ()),
+                Some(negamax(&game, depth)),
+                "depth {}",
+                depth
+            );
+        }
+    }
+
+    #[test]
+    fn test_quiescence_searches_noisy_moves_only() {
+        // Noisy moves lead to the leaves, but the player may stand pat at the value of the
+        // position itself
+        let tree = HandTree::node(vec![HandTree::leaf(-5), HandTree::leaf(2)]).with_value(1).noisy();
+        assert_eq!(quiescence(&tree, -INFINITY, INFINITY, &mut ()), Some(5));
+        assert_eq!(negamax(&tree, 0), 5);
+        let tree = HandTree::node(vec![HandTree::leaf(3)]).with_value(1).noisy();
+        assert_eq!(quiescence(&tree, -INFINITY, INFINITY, &mut ()), Some(1));
+    }
+
+    #[test]
+    fn test_observer_abandons_search() {
+        let mut counter = Counter {
+            max_nodes: Some(100),
+            ..Counter::default()
+        };
+        assert_eq!(alpha_beta(&TicTacToe::default(), 9, -INFINITY, INFINITY, &mut counter), None);
+        assert_eq!(counter.nodes, 101);
+    }
+}
@@ -0,0 +1,155 @@
+//! Search for games of more than two players, where a position has a value for each of them.
+//!
+//! Max-n lets every player pick the move best for themselves. Paranoid search assumes that
+//! all other players are against the one it searches for, which turns the game into a two
+//! player one, so that alpha-beta pruning applies again.
+
+/// A game of any number of players, who move in turns.
+pub trait MultiPlayerGame: Sized {
+    type Move: Copy;
+
+    fn players(&self) -> usize;
+
+    /// The player to move, from 0 to `players() - 1`.
+    fn to_move(&self) -> usize;
+
+    /// The moves of the player to move, searched in the order they are returned.
+    fn moves(&self) -> Vec<Self::Move>;
+
+    fn apply(&self, mv: Self::Move) -> Self;
+
+    /// Whether the game is over. By default it is when there are no moves left.
+    fn is_terminal(&self) -> bool {
+        self.moves().is_empty()
+    }
+
+    /// Value of the position for every player, indexed by player. In a terminal position
+    /// that is the result of the game.
+    fn evaluate(&self) -> Vec<i32>;
+}
+
+/// Values of the position for every player, searched to `depth` moves with max-n: the player
+/// to move takes the move with the highest value for themselves, the first one of equals.
+pub fn max_n<G: MultiPlayerGame>(game: &G, depth: u32) -> Vec<i32> {
+    match max_n_best_move(game, depth) {
+        Some((_, values)) => values,
+        None => game.evaluate(),
+    }
+}
+
+/// The max-n move of the player to move with the values it leads to, or `None` if the game
+/// is over or `depth` is 0.
+pub fn max_n_best_move<G: MultiPlayerGame>(game: &G, depth: u32) -> Option<(G::Move, Vec<i32>)> {
+    if depth == 0 || game.is_terminal() {
+        return None;
+    }
+    let player = game.to_move();
+    let mut best: Option<(G::Move, Vec<i32>)> = None;
+    for mv in game.moves() {
+        let values = max_n(&game.apply(mv), depth - 1);
+        if best.as_ref().is_none_or(|(_, best_values)| values[player] > best_values[player]) {
+            best = Some((mv, values));
+        }
+    }
+    best
+}
+
+/// Value of the position for `player`, searched to `depth` moves as if all other players
+/// were trying to minimize it.
+pub fn paranoid<G: MultiPlayerGame>(game: &G, depth: u32, player: usize) -> i32 {
+    paranoid_search(game, depth, player, i32::MIN, i32::MAX)
+}
+
+/// The paranoid move of the player to move with its value for them, or `None` if the game
+/// is over or `depth` is 0.
+pub fn paranoid_best_move<G: MultiPlayerGame>(game: &G, depth: u32) -> Option<(G::Move, i32)> {
+    if depth == 0 || game.is_terminal() {
+        return None;
+    }
+    let player = game.to_move();
+    let mut best: Option<(G::Move, i32)> = None;
+    for mv in game.moves() {
+        let alpha = best.map_or(i32::MIN, |(_, value)| value);
+        let value = paranoid_search(&game.apply(mv), depth - 1, player, alpha, i32::MAX);
+        if best.is_none_or(|(_, best_value)| value > best_value) {
+            best = Some((mv, value));
+        }
+    }
+    best
+}
+
+fn paranoid_search<G: MultiPlayerGame>(game: &G, depth: u32, player: usize, mut alpha: i32, mut beta: i32) -> i32 {
+    if depth == 0 || game.is_terminal() {
+        return game.evaluate()[player];
+    }
+    let maximizing = game.to_move() == player;
+    let mut best = if maximizing { i32::MIN } else { i32::MAX };
+    for mv in game.moves() {
+        let value = paranoid_search(&game.apply(mv), depth - 1, player, alpha, beta);
+        if maximizing {
+            best = best.max(value);
+            alpha = alpha.max(value);
+        } else {
+            best = best.min(value);
+            beta = beta.min(value);
+        }
+        if alpha >= beta {
+            break;
+        }
+    }
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::games::TakeAway;
+
+    #[test]
+    fn test_max_n_lets_every_player_win_for_themselves() {
+        // Whoever faces 3 tokens lets the next player win, whoever faces 4 the one after,
+        // so the first player takes one token of 5 and wins
+        assert_eq!(max_n(&TakeAway::new(3, 3), 10), vec![0, 1, 0]);
+        assert_eq!(max_n(&TakeAway::new(4, 3), 10), vec![0, 0, 1]);
+        assert_eq!(max_n_best_move(&TakeAway::new(5, 3), 10), Some((1, vec![1, 0, 0])));
+    }
+
+    #[test]
+    fn test_paranoid_player_only_wins_by_taking_the_last_token_at_once() {
+        // The two others can always take the last token between them
+        assert_eq!(paranoid_best_move(&TakeAway::new(2, 3), 10), Some((2, 1)));
+        for pile in 3..8 {
+            assert_eq!(paranoid(&TakeAway::new(pile, 3), 10, 0), 0, "pile {}", pile);
+        }
+    }
+
+    #[test]
+    fn test_paranoid_pruning_keeps_value_of_unpruned_search() {
+        // Without pruning, the minimum of the opponents and the maximum of the player
+        fn plain(game: &TakeAway, depth: u32, player: usize) -> i32 {
+            if depth == 0 || game.is_terminal() {
+                return game.evaluate()[player];
+            }
+            let values = game.moves().into_iter().map(|mv| plain(&game.apply(mv), depth - 1, player));
+            if game.to_move() == player {
+                values.max().unwrap()
+            } else {
+                values.min().unwrap()
+            }
+        }
+        for pile in 1..10 {
+            for player in 0..3 {
+                let game = TakeAway::new(pile, 3);
+                assert_eq!(paranoid(&game, 6, player), plain(&game, 6, player));
+            }
+        }
+    }
+
+    #[test]
+    fn test_depth_limit_takes_evaluation() {
+        let game = TakeAway::new(5, 3);
+        assert_eq!(max_n(&game, 0), vec![0, 0, 0]);
+        assert_eq!(max_n_best_move(&game, 0), None);
+        assert_eq!(paranoid(&game, 0, 1), 0);
+    }
+}
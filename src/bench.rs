@@ -0,0 +1,273 @@
+use chess::{Board, BoardStatus};
+use std::hint::black_box;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::engine::parallel::{new_parallel_search, ParallelMode};
+use crate::engine::search::{Search, SearchOptions, MAX_DEPTH};
+use crate::engine::transposition::{TranspositionTable, DEFAULT_HASH_SIZE_MB};
+use crate::engine::tree::Tree;
+
+mod rc_tree;
+
+const POSITIONS: [&str; 6] = [
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4",
+    "2r2rk1/p1qnbppp/1p1ppn2/6N1/2PQ4/2N3P1/PP2PPKP/R1B2R2 w - - 3 14",
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+    "4r2k/4p3/1p6/q1p5/1Pp2Np1/pPP2pPp/P4P1P/7K w - - 0 1",
+];
+
+// Shape of the tree built by the tree benchmark, about the size of a short search
+const TREE_BRANCHING: usize = 8;
+const TREE_DEPTH: usize = 6;
+const TREE_ROUNDS: usize = 5;
+// Nodes of that tree, the root included: 1 + 8 + 8^2 + ... + 8^6
+const TREE_NODES: usize = (TREE_BRANCHING.pow(TREE_DEPTH as u32 + 1) - 1) / (TREE_BRANCHING - 1);
+
+// Long enough for any of the positions to reach the benchmark depth
+const TIME_TO_DEPTH_LIMIT: Duration = Duration::from_secs(3600);
+
+// ProbCut margins compared by the ProbCut benchmark, and how many moves it plays from each
+// of the positions
+const PROBCUT_MARGINS: [u16; 5] = [0, 50, 100, 200, 400];
+const SELF_PLAY_MOVES: usize = 40;
+
+/// Searches a fixed set of positions in every parallel mode with 1, 2, 4, ... threads up to
+/// the number of cpus, and prints the node throughput with the given time per position, and
+/// the total time needed to reach the given depth.
+pub fn run(move_time: Duration, depth: u8) {
+    let mut thread_counts = vec![];
+    let mut threads = 1;
+    while threads < num_cpus::get() {
+        thread_counts.push(threads);
+        threads *= 2;
+    }
+    thread_counts.push(num_cpus::get());
+
+    println!("mode           threads        nodes          nps   speedup   depth {:2} (ms)", depth);
+    for mode in ParallelMode::ALL {
+        let mut single_thread_nps = None;
+        for &threads in thread_counts.iter() {
+            let mut nodes = 0;
+            let mut time_to_depth = Duration::ZERO;
+            for fen in POSITIONS {
+                let board = Board::from_str(fen).unwrap();
+                let mut search = new_parallel_search(mode, &board, new_table(), threads);
+                search.run_timed(MAX_DEPTH, move_time);
+                nodes += search.nodes();
+
+                let start = Instant::now();
+                new_parallel_search(mode, &board, new_table(), threads).run_timed(depth, TIME_TO_DEPTH_LIMIT);
+                time_to_depth += start.elapsed();
+            }
+            let nps = nodes * 1000 / (move_time.as_millis() as u64 * POSITIONS.len() as u64);
+            let speedup = nps as f64 / *single_thread_nps.get_or_insert(nps) as f64;
+            println!(
+                "{:14} {:7} {:12} {:12} {:9.2} {:15}",
+                mode.name(),
+                threads,
+                nodes,
+                nps,
+                speedup,
+                time_to_depth.as_millis()
+            );
+        }
+    }
+}
+
+/// Searches the fixed set of positions single threaded with the given time per position,
+/// with and without late move reductions, and prints the depth reached in each.
+pub fn depth(move_time: Duration) {
+    println!("position   without lmr   with lmr");
+    let mut totals = [0, 0];
+    for (i, fen) in POSITIONS.iter().enumerate() {
+        let board = Board::from_str(fen).unwrap();
+        let mut depths = [0, 0];
+        for (late_move_reductions, depth) in [false, true].into_iter().zip(depths.iter_mut()) {
+            let mut search = Search::with_table(&board, board.side_to_move(), new_table());
+            search.set_options(SearchOptions {
+                late_move_reductions,
+                ..SearchOptions::default()
+            });
+            search.run_timed(MAX_DEPTH, move_time);
+            *depth = search.completed_depth();
+        }
+        totals[0] += depths[0] as u32;
+        totals[1] += depths[1] as u32;
+        println!("{:8} {:13} {:10}", i + 1, depths[0], depths[1]);
+    }
+    println!("{:>8} {:13} {:10}", "total", totals[0], totals[1]);
+}
+
+/// Plays on from each of the fixed set of positions against itself with the given time per
+/// move, once for every ProbCut margin, and prints how many of the checked ProbCut and
+/// multi-cut predictions were right.
+pub fn probcut(move_time: Duration) {
+    println!("margin   probcut right/checked   multi-cut right/checked");
+    for margin in PROBCUT_MARGINS {
+        let (mut probcut_right, mut probcut_checked) = (0, 0);
+        let (mut multi_cut_right, mut multi_cut_checked) = (0, 0);
+        for fen in POSITIONS {
+            let mut board = Board::from_str(fen).unwrap();
+            let table = new_table();
+            for _ in 0..SELF_PLAY_MOVES {
+                if board.status() != BoardStatus::Ongoing {
+                    break;
+                }
+                let mut search = Search::with_table(&board, board.side_to_move(), Arc::clone(&table));
+                search.set_options(SearchOptions {
+                    probcut_margin: margin,
+                    ..SearchOptions::default()
+                });
+                let best_move = search.run_timed(MAX_DEPTH, move_time);
+                let statistics = search.statistics();
+                probcut_right += statistics.probcut_right;
+                probcut_checked += statistics.probcut_checked;
+                multi_cut_right += statistics.multi_cut_right;
+                multi_cut_checked += statistics.multi_cut_checked;
+                board = board.make_move_new(best_move);
+            }
+        }
+        println!(
+            "{:6} {:13}/{:<9} {:15}/{}",
+            margin, probcut_right, probcut_checked, multi_cut_right, multi_cut_checked
+        );
+    }
+}
+
+fn new_table() -> Arc<TranspositionTable> {
+    Arc::new(TranspositionTable::new(DEFAULT_HASH_SIZE_MB))
+}
+
+/// Builds, walks and drops trees the way a search does, depth first, once with the
+/// `Rc<RefCell>` tree the search used before and once with the arena tree, and prints the
+/// time each of those takes per node.
+///
+/// On release builds the arena tree builds a little slower, about 105 against 92 ns/node,
+/// but walks and drops in about 20 ns/node where the `Rc<RefCell>` tree takes about 54.
+pub fn tree() {
+    let rc_refcell = measure_tree(|| rc_tree::Tree::new([0u64; 4]));
+    let arena = measure_tree(|| Tree::new([0u64; 4]));
+    println!("nodes: {}", TREE_NODES);
+    println!("          rc_refcell        arena");
+    for (name, rc_refcell, arena) in [
+        ("build", rc_refcell[0], arena[0]),
+        ("walk", rc_refcell[1], arena[1]),
+        ("drop", rc_refcell[2], arena[2]),
+    ] {
+        println!("{:5} {:8.1} ns/node {:6.1} ns/node", name, rc_refcell, arena);
+    }
+}
+
+/// The nanoseconds per node it takes to build, walk and drop the trees made by `new_tree`.
+fn measure_tree<B: BenchTree>(new_tree: impl Fn() -> B) -> [f64; 3] {
+    let (mut building, mut walking, mut dropping) = (Duration::ZERO, Duration::ZERO, Duration::ZERO);
+    for _ in 0..TREE_ROUNDS {
+        let start = Instant::now();
+        let mut tree = new_tree();
+        build(&mut tree, TREE_DEPTH);
+        building += start.elapsed();
+
+        let start = Instant::now();
+        black_box(walk(&mut tree, TREE_DEPTH));
+        walking += start.elapsed();
+
+        let start = Instant::now();
+        drop(tree);
+        dropping += start.elapsed();
+    }
+    let per_node = |time: Duration| time.as_nanos() as f64 / (TREE_NODES * TREE_ROUNDS) as f64;
+    [per_node(building), per_node(walking), per_node(dropping)]
+}
+
+/// What the tree benchmark does with a tree, with the cursor at the root before and after.
+trait BenchTree {
+    fn add_child(&mut self, data: [u64; 4]);
+    fn current_data(&self) -> [u64; 4];
+    fn number_of_children(&self) -> usize;
+    fn goto_child(&mut self, i: usize);
+    fn goto_last_child(&mut self);
+    fn goto_parent(&mut self);
+}
+
+impl BenchTree for Tree<[u64; 4]> {
+    fn add_child(&mut self, data: [u64; 4]) {
+        Tree::add_child(self, data);
+    }
+
+    fn current_data(&self) -> [u64; 4] {
+        self.current().data
+    }
+
+    fn number_of_children(&self) -> usize {
+        Tree::number_of_children(self)
+    }
+
+    fn goto_child(&mut self, i: usize) {
+        Tree::goto_child(self, i);
+    }
+
+    fn goto_last_child(&mut self) {
+        Tree::goto_last_child(self);
+    }
+
+    fn goto_parent(&mut self) {
+        Tree::goto_parent(self);
+    }
+}
+
+impl BenchTree for rc_tree::Tree<[u64; 4]> {
+    fn add_child(&mut self, data: [u64; 4]) {
+        rc_tree::Tree::add_child(self, data);
+    }
+
+    fn current_data(&self) -> [u64; 4] {
+        rc_tree::Tree::current_data(self)
+    }
+
+    fn number_of_children(&self) -> usize {
+        rc_tree::Tree::number_of_children(self)
+    }
+
+    fn goto_child(&mut self, i: usize) {
+        rc_tree::Tree::goto_child(self, i);
+    }
+
+    fn goto_last_child(&mut self) {
+        rc_tree::Tree::goto_last_child(self);
+    }
+
+    fn goto_parent(&mut self) {
+        rc_tree::Tree::goto_parent(self);
+    }
+}
+
+fn build(tree: &mut impl BenchTree, depth: usize) {
+    if depth == 0 {
+        return;
+    }
+    for i in 0..TREE_BRANCHING {
+        tree.add_child([i as u64; 4]);
+        tree.goto_last_child();
+        build(tree, depth - 1);
+        tree.goto_parent();
+    }
+}
+
+/// Visits every node below the current one, adding up their data so the visits are not
+/// optimized away.
+fn walk(tree: &mut impl BenchTree, depth: usize) -> u64 {
+    let mut sum = tree.current_data()[0];
+    if depth == 0 {
+        return sum;
+    }
+    for i in 0..tree.number_of_children() {
+        tree.goto_child(i);
+        sum = sum.wrapping_add(walk(tree, depth - 1));
+        tree.goto_parent();
+    }
+    sum
+}
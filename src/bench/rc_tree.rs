@@ -0,0 +1,75 @@
+//! The search tree as it was before the arena tree, with every node behind an
+//! `Rc<RefCell>`, kept only for the tree benchmark to compare against.
+
+use std::cell::RefCell;
+use std::rc::{Rc, Weak};
+
+pub struct Node<T> {
+    data: T,
+    children: Vec<Rc<RefCell<Node<T>>>>,
+    parent: Option<Weak<RefCell<Node<T>>>>,
+    // Never read here, kept so the nodes are as big as the search's were
+    #[allow(dead_code)]
+    index: Option<usize>,
+}
+
+impl<T> Node<T> {
+    fn new_with_parent(data: T, parent: Option<Weak<RefCell<Node<T>>>>, index: Option<usize>) -> Node<T> {
+        Node {
+            data,
+            children: vec![],
+            parent,
+            index,
+        }
+    }
+}
+
+pub struct Tree<T> {
+    // Holds the tree, the children below it only point back up weakly
+    _root: Rc<RefCell<Node<T>>>,
+    current: Rc<RefCell<Node<T>>>,
+}
+
+impl<T> Tree<T> {
+    pub fn new(data: T) -> Tree<T> {
+        let root = Rc::new(RefCell::new(Node::new_with_parent(data, None, None)));
+        Tree {
+            _root: Rc::clone(&root),
+            current: root,
+        }
+    }
+
+    pub fn add_child(&self, data: T) {
+        let child = Rc::new(RefCell::new(Node::new_with_parent(
+            data,
+            Some(Rc::downgrade(&self.current)),
+            Some(self.number_of_children()),
+        )));
+        self.current.borrow_mut().children.push(child);
+    }
+
+    pub fn current_data(&self) -> T
+    where
+        T: Copy,
+    {
+        self.current.borrow().data
+    }
+
+    pub fn number_of_children(&self) -> usize {
+        self.current.borrow().children.len()
+    }
+
+    pub fn goto_child(&mut self, i: usize) {
+        self.current = Rc::clone(&Rc::clone(&self.current).borrow().children[i]);
+    }
+
+    pub fn goto_last_child(&mut self) {
+        let last_child_i = self.current.borrow().children.len() - 1;
+        self.goto_child(last_child_i);
+    }
+
+    pub fn goto_parent(&mut self) {
+        let parent = self.current.borrow().parent.as_ref().and_then(Weak::upgrade);
+        self.current = parent.expect("no parent to go to");
+    }
+}
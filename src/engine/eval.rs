@@ -1,48 +1,108 @@
-use chess::{Board, BoardStatus, ChessMove, Color, MoveGen, Piece};
+use chess::{Board, BoardStatus, ChessMove, Color, Piece};
+
+pub const CHECKMATE_EVAL: i16 = 10000;
+// Mates are scored CHECKMATE_EVAL less the plies to them, and no mate is further than this
+const MAX_MATE_PLY: i16 = 256;
+/// Scores from here up are mates for the side to move, and from its negation down mates against it.
+pub const MATE_THRESHOLD: i16 = CHECKMATE_EVAL - MAX_MATE_PLY;
+
+pub fn piece_value(piece: Piece) -> i16 {
+    match piece {
+        Piece::Queen => 900,
+        Piece::Rook => 500,
+        Piece::Bishop => 310,
+        Piece::Knight => 290,
+        Piece::Pawn => 100,
+        Piece::King => 0,
+    }
+}
 
-const CHECKMATE_EVAL: i16 = 10000;
+/// Value of the pieces of `color` other than pawns and the king.
+pub fn non_pawn_material(board: &Board, color: Color) -> i16 {
+    [Piece::Queen, Piece::Rook, Piece::Bishop, Piece::Knight]
+        .into_iter()
+        .map(|piece| piece_value(piece) * (board.color_combined(color) & board.pieces(piece)).popcnt() as i16)
+        .sum()
+}
 
-pub fn eval_with_children(start_board: &Board, moves: &Vec<ChessMove>, color: Color) -> (i16, i16) {
-    let board = make_moves(start_board, moves);
-    if board.status() == BoardStatus::Ongoing {
-        let evals: Vec<i16> = MoveGen::new_legal(&board)
-            .map(|mv| eval_one(&Board::make_move_new(&board, mv), color))
-            .collect();
-        (*evals.iter().min().unwrap(), *evals.iter().max().unwrap())
+/// Score of a draw for the side to move in `board`, worse than equal by `contempt` if that
+/// is `color`, the side the search is for, and better by it otherwise.
+pub fn draw_score(board: &Board, color: Color, contempt: i16) -> i16 {
+    if board.side_to_move() == color {
+        -contempt
     } else {
-        let result = eval_one(&board, color);
-        (result, result)
+        contempt
     }
 }
 
-pub fn eval(start_board: &Board, moves: &Vec<ChessMove>, color: Color) -> i16 {
-
+pub fn eval(start_board: &Board, moves: &[ChessMove], color: Color) -> i16 {
     let board = make_moves(start_board, moves);
     eval_one(&board, color)
 }
 
-fn eval_one(board: &Board, color: Color) -> i16 {
-    let score: i16;
+/// Score of being checkmated `ply` plies from the root of the search.
+#[cfg(test)]
+pub fn mated_in(ply: usize) -> i16 {
+    -CHECKMATE_EVAL + ply as i16
+}
+
+pub fn is_mate_score(score: i16) -> bool {
+    (MATE_THRESHOLD as u16..=CHECKMATE_EVAL as u16).contains(&score.unsigned_abs())
+}
 
+/// Turns a score counting the plies to mate from the root into one counting them from a
+/// position `ply` plies after the root, as the table and a new root need them. Other scores
+/// stay as they are.
+pub fn mate_score_from_root(score: i16, ply: usize) -> i16 {
+    if !is_mate_score(score) {
+        score
+    } else if score > 0 {
+        score + ply as i16
+    } else {
+        score - ply as i16
+    }
+}
+
+/// Turns a score counting the plies to mate from a position `ply` plies after the root into
+/// one counting them from the root.
+pub fn mate_score_to_root(score: i16, ply: usize) -> i16 {
+    if !is_mate_score(score) {
+        score
+    } else if score > 0 {
+        score - ply as i16
+    } else {
+        score + ply as i16
+    }
+}
+
+/// The number of moves to a mate score, negative when the side to move gets mated, as the
+/// UCI `score mate` wants it.
+pub fn mate_in_moves(score: i16) -> i16 {
+    if score > 0 {
+        (CHECKMATE_EVAL - score + 1) / 2
+    } else {
+        -(CHECKMATE_EVAL + score) / 2
+    }
+}
+
+fn eval_one(board: &Board, color: Color) -> i16 {
     if board.status() == BoardStatus::Stalemate {
         return 0;
     }
 
-    match eval_checkmate(board) {
-        Some(val) => score = val,
-        None => {
-            score = eval_material(board);
-        }
-    }
+    let score = match eval_checkmate(board) {
+        Some(val) => val,
+        None => eval_material(board),
+    };
     score * (1 - 2 *((color == Color::Black) as i16))
 }
 
-fn make_moves(start_board: &Board, moves: &Vec<ChessMove>) -> Board {
-    let mut board = start_board.clone();
+fn make_moves(start_board: &Board, moves: &[ChessMove]) -> Board {
+    let mut board = *start_board;
     for mv in moves {
         board = board.make_move_new(*mv);
     }
-    return board;
+    board
 }
 
 fn eval_checkmate(board: &Board) -> Option<i16> {
@@ -69,20 +129,12 @@ fn eval_material(board: &Board) -> i16 {
             let pieces = board.color_combined(color) & board.pieces(piece);
             let num_pieces = pieces.popcnt() as i16;
 
-            let multiplier;
-            match color {
-                Color::White => multiplier = 1,
-                Color::Black => multiplier = -1,
-            }
-
-            match piece {
-                Piece::Queen => score += multiplier * 900 * num_pieces,
-                Piece::Rook => score += multiplier * 500 * num_pieces,
-                Piece::Bishop => score += multiplier * 310 * num_pieces,
-                Piece::Knight => score += multiplier * 290 * num_pieces,
-                Piece::Pawn => score += multiplier * 100 * num_pieces,
-                _ => {}
-            }
+            let multiplier = match color {
+                Color::White => 1,
+                Color::Black => -1,
+            };
+
+            score += multiplier * piece_value(piece) * num_pieces;
         }
     }
     score
@@ -113,10 +165,8 @@ mod tests {
             CastleRights::NoRights,
             Color::White,
         );
-        assert_eq!(eval(&board, &vec![], Color::White), 100);
-        assert_eq!(eval(&board, &vec![], Color::Black), -100);
-        assert_eq!(eval_with_children(&board, &vec![], Color::White), (100, 100));
-        assert_eq!(eval_with_children(&board, &vec![], Color::Black), (-100, -100));
+        assert_eq!(eval(&board, &[], Color::White), 100);
+        assert_eq!(eval(&board, &[], Color::Black), -100);
     }
 
     #[test]
@@ -139,8 +189,7 @@ mod tests {
             Color::Black,
         );
         assert_eq!(board.status(), BoardStatus::Stalemate);
-        assert_eq!(eval(&board, &vec![], Color::White), 0);
-        assert_eq!(eval_with_children(&board, &vec![], Color::White), (0, 0));
+        assert_eq!(eval(&board, &[], Color::White), 0);
     }
 
     #[test]
@@ -163,37 +212,24 @@ mod tests {
             Color::Black,
         );
         assert_eq!(board.status(), BoardStatus::Checkmate);
-        assert_eq!(eval(&board, &vec![], Color::White), CHECKMATE_EVAL);
-        assert_eq!(eval(&board, &vec![], Color::Black), -CHECKMATE_EVAL);
-        assert_eq!(
-            eval_with_children(&board, &vec![], Color::White),
-            (CHECKMATE_EVAL, CHECKMATE_EVAL)
-        );
-        assert_eq!(
-            eval_with_children(&board, &vec![], Color::Black),
-            (-CHECKMATE_EVAL, -CHECKMATE_EVAL)
-        );
+        assert_eq!(eval(&board, &[], Color::White), CHECKMATE_EVAL);
+        assert_eq!(eval(&board, &[], Color::Black), -CHECKMATE_EVAL);
     }
 
-    fn test_getting_right_min_and_max() {
-        let textboard = r#"
-        8|   |   |   |   | ♔ |   |   |   |
-        7|   |   |   |   | ♙ |   |   |   |
-        6|   |   |   |   |   |   |   |   |
-        5|   |   |   |   |   |   |   |   |
-        4|   |   |   |   |   |   |   |   |
-        3|   |   |   |   |   |   | ♙ |   |
-        2|   |   |   |   | ♟︎ | ♟︎ |   |   |
-        1|   |   |   |   | ♚ |   |   |   |
-        a   b   c   d   e   f   g   h 
-        "#;
-        let board = board_from_textboard(
-            textboard,
-            CastleRights::NoRights,
-            CastleRights::NoRights,
-            Color::White,
-        );
-        assert_eq!(eval_with_children(&board, &vec![], Color::White), (0, 100));
-        assert_eq!(eval_with_children(&board, &vec![], Color::Black), (0, -100));
+    #[test]
+    fn test_mate_scores_count_plies() {
+        // Mating with the third ply from the root is mate in two moves
+        let score = mate_score_to_root(CHECKMATE_EVAL, 3);
+        assert_eq!(score, CHECKMATE_EVAL - 3);
+        assert_eq!(mate_in_moves(score), 2);
+        assert_eq!(mate_in_moves(mated_in(4)), -2);
+        assert_eq!(mate_score_from_root(score, 1), CHECKMATE_EVAL - 2);
+        assert_eq!(mate_score_from_root(mated_in(4), 4), -CHECKMATE_EVAL);
+
+        assert!(is_mate_score(mated_in(64)));
+        assert!(!is_mate_score(900));
+        assert_eq!(mate_score_from_root(900, 3), 900);
+        assert!(!is_mate_score(i16::MAX));
+        assert!(!is_mate_score(-i16::MAX));
     }
 }
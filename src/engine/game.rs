@@ -0,0 +1,163 @@
+use chess::{Board, BoardStatus, ChessMove, Color, MoveGen, Piece, EMPTY};
+use minimax::Game;
+
+use crate::engine::eval::{draw_score, eval, mate_score_to_root};
+use crate::engine::ordering::{is_capture, mvv_lva};
+use crate::engine::see::see;
+
+/// Chess as a game of the minimax library. Captures and queen promotions that do not lose
+/// material by static exchange evaluation are its noisy moves, ordered by most valuable
+/// victim and least valuable attacker, and every evasion is noisy while in check. Mates
+/// are scored by their distance from the position the game was created at, `ply` plies
+/// after the root of the search, and stalemates as draws with the contempt of `color`.
+#[derive(Clone, Copy, Debug)]
+pub struct ChessGame {
+    board: Board,
+    ply: usize,
+    quiet_checks: bool,
+    color: Color,
+    contempt: i16,
+}
+
+impl ChessGame {
+    #[cfg(test)]
+    pub fn new(board: Board) -> ChessGame {
+        ChessGame::at_ply(board, 0)
+    }
+
+    pub fn at_ply(board: Board, ply: usize) -> ChessGame {
+        ChessGame {
+            board,
+            ply,
+            quiet_checks: false,
+            color: board.side_to_move(),
+            contempt: 0,
+        }
+    }
+
+    /// Makes stalemates worse than equal by `contempt` for `color`, and better for its opponent.
+    pub fn with_contempt(self, color: Color, contempt: i16) -> ChessGame {
+        ChessGame { color, contempt, ..self }
+    }
+
+    /// Makes the quiet moves that give check noisy too, after the captures, in this position
+    /// only, so that a quiescence search started here sees mates just past the horizon.
+    pub fn with_quiet_checks(self) -> ChessGame {
+        ChessGame { quiet_checks: true, ..self }
+    }
+
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    fn in_check(&self) -> bool {
+        *self.board.checkers() != EMPTY
+    }
+}
+
+impl Game for ChessGame {
+    type Move = ChessMove;
+
+    fn moves(&self) -> Vec<ChessMove> {
+        MoveGen::new_legal(&self.board).collect()
+    }
+
+    fn apply(&self, mv: ChessMove) -> ChessGame {
+        ChessGame {
+            board: self.board.make_move_new(mv),
+            ply: self.ply + 1,
+            quiet_checks: false,
+            ..*self
+        }
+    }
+
+    fn is_terminal(&self) -> bool {
+        self.board.status() != BoardStatus::Ongoing
+    }
+
+    fn evaluate(&self) -> i32 {
+        if self.board.status() == BoardStatus::Stalemate {
+            return draw_score(&self.board, self.color, self.contempt) as i32;
+        }
+        let score = eval(&self.board, &[], self.board.side_to_move());
+        mate_score_to_root(score, self.ply) as i32
+    }
+
+    fn noisy_moves(&self) -> Vec<ChessMove> {
+        let in_check = self.in_check();
+        let board = &self.board;
+        let mut moves: Vec<ChessMove> = MoveGen::new_legal(board)
+            .filter(|mv| {
+                in_check
+                    || (is_capture(board, mv) || mv.get_promotion() == Some(Piece::Queen)) && see(board, *mv) >= 0
+            })
+            .collect();
+        moves.sort_by_key(|mv| -mvv_lva(board, mv));
+        if self.quiet_checks && !in_check {
+            moves.extend(MoveGen::new_legal(board).filter(|mv| {
+                !is_capture(board, mv) && mv.get_promotion().is_none() && *board.make_move_new(*mv).checkers() != EMPTY
+            }));
+        }
+        moves
+    }
+
+    fn can_stand_pat(&self) -> bool {
+        !self.in_check()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_noisy_moves_are_captures_ordered_by_victim() {
+        // The knight on c3 can take the queen on a2 or the pawn on b5
+        let board = Board::from_str("4k3/8/8/1p6/8/2N5/q7/4K3 w - - 0 1").unwrap();
+        let game = ChessGame::new(board);
+        let noisy = game.noisy_moves();
+        assert_eq!(noisy.len(), 2);
+        assert_eq!(noisy[0].to_string(), "c3a2");
+        assert!(game.can_stand_pat());
+    }
+
+    #[test]
+    fn test_losing_captures_are_not_noisy() {
+        // The pawn on d5 is defended by the one on e6, the one on b5 is not
+        let board = Board::from_str("4k3/8/4p3/1p1p4/8/3Q4/8/4K3 w - - 0 1").unwrap();
+        let noisy = ChessGame::new(board).noisy_moves();
+        assert_eq!(noisy.len(), 1);
+        assert_eq!(noisy[0].to_string(), "d3b5");
+    }
+
+    #[test]
+    fn test_quiet_checks_are_noisy_only_where_asked() {
+        // Rd1 mates, Re2 is a quiet move without check
+        let board = Board::from_str("3r2k1/8/8/8/8/8/5PPP/6K1 b - - 0 1").unwrap();
+        assert!(ChessGame::new(board).noisy_moves().is_empty());
+        let game = ChessGame::new(board).with_quiet_checks();
+        let noisy: Vec<String> = game.noisy_moves().iter().map(|mv| mv.to_string()).collect();
+        assert_eq!(noisy, ["d8d1"]);
+        assert!(game.apply(ChessMove::from_str("g8f8").unwrap()).noisy_moves().is_empty());
+    }
+
+    #[test]
+    fn test_every_evasion_is_noisy_in_check() {
+        let board = Board::from_str("4k3/8/8/8/8/8/4r3/4K3 w - - 0 1").unwrap();
+        let game = ChessGame::new(board);
+        assert!(!game.can_stand_pat());
+        assert_eq!(game.noisy_moves().len(), game.moves().len());
+    }
+
+    #[test]
+    fn test_stalemate_is_scored_with_contempt() {
+        // Qf7 stalemates the king on h8
+        let board = Board::from_str("7k/8/6K1/8/8/8/5Q2/8 w - - 0 1").unwrap();
+        let game = ChessGame::new(board).with_contempt(Color::White, 30);
+        let stalemate = game.apply(ChessMove::from_str("f2f7").unwrap());
+        assert!(stalemate.is_terminal());
+        assert_eq!(stalemate.evaluate(), 30);
+        assert_eq!(stalemate.with_contempt(Color::Black, 30).evaluate(), -30);
+    }
+}
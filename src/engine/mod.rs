@@ -1,33 +1,107 @@
-use chess::{Board, ChessMove, Color, Game, MoveGen};
+use chess::{Board, ChessMove, Color};
 use log::info;
 use std::str::FromStr;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
 use std::sync::mpsc;
 use std::sync::mpsc::{Receiver, SyncSender};
 use std::thread;
 use std::thread::JoinHandle;
 use std::time::Duration;
 use vampirc_uci::Duration as VampDuration;
-use vampirc_uci::{UciMessage, UciTimeControl};
+use vampirc_uci::{parse_one, UciMessage, UciOptionConfig, UciSearchControl, UciTimeControl};
 
-use crate::engine::search::{Search};
+use crate::engine::parallel::{new_parallel_search, ParallelMode};
+use crate::engine::search::{
+    GameHistory, Search, SearchLimits, SearchOptions, SharedState, DEFAULT_LMR_BASE, DEFAULT_LMR_DIVISOR,
+    DEFAULT_PROBCUT_MARGIN, DEFAULT_TREE_MEMORY_MB, MAX_CONTEMPT, MAX_DEPTH, MAX_LMR_BASE, MAX_LMR_DIVISOR,
+    MAX_PROBCUT_MARGIN, MAX_TREE_MEMORY_MB, MIN_LMR_DIVISOR, MIN_TREE_MEMORY_MB,
+};
+use crate::engine::transposition::{TranspositionTable, DEFAULT_HASH_SIZE_MB, MAX_HASH_SIZE_MB};
 use crate::engine::utils::show_board;
 
 pub mod eval;
+pub mod game;
+pub mod ordering;
+pub mod parallel;
 pub mod search;
+pub mod see;
+pub mod transposition;
 pub mod tree;
 pub mod utils;
 
+const MAX_THREADS: usize = 256;
+const PONDER_WAIT: Duration = Duration::from_millis(2);
+
+/// A message for the engine. `vampirc_uci` drops the `ponder` of a go command that also
+/// gives the clock, so the flag is kept here next to the message.
+pub struct Command {
+    pub message: UciMessage,
+    pub ponder: bool,
+}
+
+pub fn parse_command(line: &str) -> Command {
+    let message = parse_one(line);
+    let ponder = matches!(message, UciMessage::Go { .. })
+        && line.split_whitespace().any(|word| word.eq_ignore_ascii_case("ponder"));
+    Command { message, ponder }
+}
+
+// A position as given by the position command: the starting fen and the moves played
+type GamePosition = (Option<String>, Vec<ChessMove>);
+
 pub struct Engine {
     board: Option<Board>,
-    channel_sender: SyncSender<UciMessage>,
-    channel_receiver: Receiver<UciMessage>,
+    // The game that led to the board
+    history: GameHistory,
+    position: Option<GamePosition>,
+    // The position of the last search, whose tree the search thread keeps
+    searched_position: Option<GamePosition>,
+    table: Arc<TranspositionTable>,
+    threads: usize,
+    parallel_mode: ParallelMode,
+    search_options: SearchOptions,
+    // Stop flag of the running search, and the signal that its best move has been replied
+    search: Option<(Arc<SharedState>, Receiver<()>)>,
+    search_jobs: SyncSender<SearchJob>,
+    channel_sender: SyncSender<Command>,
+    channel_receiver: Receiver<Command>,
+}
+
+/// Everything the search thread needs to search a position.
+struct SearchJob {
+    board: Board,
+    history: GameHistory,
+    // The moves played since the previous search, when its tree can be continued
+    played: Option<Vec<ChessMove>>,
+    depth: u8,
+    move_time: Duration,
+    limits: SearchLimits,
+    infinite: bool,
+    parallel_mode: ParallelMode,
+    table: Arc<TranspositionTable>,
+    threads: usize,
+    options: SearchOptions,
+    shared: Arc<SharedState>,
+    done: SyncSender<()>,
 }
 
 impl Default for Engine {
     fn default() -> Self {
         let (tx, rx) = mpsc::sync_channel(128);
+        let (search_jobs, jobs) = mpsc::sync_channel(1);
+        thread::spawn(move || search_thread(jobs));
         Engine {
             board: None,
+            history: GameHistory::default(),
+            position: None,
+            searched_position: None,
+            table: Arc::new(TranspositionTable::default()),
+            threads: num_cpus::get(),
+            parallel_mode: ParallelMode::LazySmp,
+            search_options: SearchOptions::default(),
+            search: None,
+            search_jobs,
             channel_sender: tx,
             channel_receiver: rx,
         }
@@ -35,17 +109,22 @@ impl Default for Engine {
 }
 
 impl Engine {
-    pub fn start(self, depth: u8) -> (JoinHandle<()>, SyncSender<UciMessage>) {
+    /// Sets the memory budget of the search trees, over all threads.
+    pub fn set_tree_memory(&mut self, size_mb: usize) {
+        self.search_options.tree_memory_mb = size_mb.clamp(MIN_TREE_MEMORY_MB, MAX_TREE_MEMORY_MB);
+    }
+
+    pub fn start(self, depth: u8) -> (JoinHandle<()>, SyncSender<Command>) {
         let sender = self.channel_sender.clone();
 
-        (thread::spawn(move || self.run(depth.clone())), sender)
+        (thread::spawn(move || self.run(depth)), sender)
     }
 
     fn run(mut self, depth: u8) {
         let timeout = Duration::from_millis(2);
         loop {
-            if let Ok(message) = self.channel_receiver.recv_timeout(timeout) {
-                if !self.handle_message(message, depth) {
+            if let Ok(command) = self.channel_receiver.recv_timeout(timeout) {
+                if !self.handle_command(command, depth) {
                     info!("Returning from event loop");
                     return;
                 }
@@ -53,12 +132,12 @@ impl Engine {
         }
     }
 
-    fn handle_message(&mut self, message: UciMessage, depth: u8) -> bool {
-        info!("rx: {}", message);
-        match message {
+    fn handle_command(&mut self, command: Command, depth: u8) -> bool {
+        info!("rx: {}", command.message);
+        match command.message {
             UciMessage::Uci => {
                 id();
-                //option
+                options();
                 uciok();
             }
             UciMessage::Debug(_) => { /*ignore for now */ }
@@ -76,57 +155,258 @@ impl Engine {
                 moves,
             } => {
                 info!("UciMessage::Position {:?}, {:?}", fen, moves);
-                let mut game = if let Some(fen) = fen {
-                    Game::from_str(fen.as_str()).unwrap()
-                } else {
-                    Game::new()
-                };
-
-                for mv in moves {
-                    game.make_move(mv);
-                }
-
-                self.board = Some(game.current_position());
+                let (board, history) = game_position(fen.as_ref().map(|fen| fen.as_str()), &moves);
+                self.board = Some(board);
+                self.history = history;
+                self.position = Some((fen.map(|fen| fen.as_str().to_string()), moves));
                 info!("Starting Board:");
                 show_board(self.board.unwrap());
             }
-            UciMessage::SetOption { .. } => {}
+            UciMessage::SetOption { name, value } => {
+                self.set_option(&name, value);
+            }
             UciMessage::UciNewGame => {
                 //create a new game
                 self.board = None;
+                self.history = GameHistory::default();
+                self.position = None;
+                self.searched_position = None;
+                self.table.clear();
+            }
+            UciMessage::Stop => {
+                self.stop_search();
             }
-            UciMessage::Stop => match &self.board {
-                None => {}
-                Some(board) => {
-                    let mut move_iter = MoveGen::new_legal(board);
-                    match move_iter.next() {
-                        Some(chess_move) => bestmove(chess_move, None),
-                        None => {}
-                    }
-                }
-            },
 
-            UciMessage::PonderHit => {}
+            UciMessage::PonderHit => {
+                // The search goes on with the work already done, now on our own clock
+                if let Some((shared, _)) = &self.search {
+                    shared.pondering.store(false, Ordering::Relaxed);
+                }
+            }
             UciMessage::Quit => {
+                self.stop_search();
                 return false;
             }
             UciMessage::Go {
                 time_control,
-                search_control: _,
+                search_control,
             } => {
-                info!("UciMessage::Go {:?}", time_control);
-                let _move_time = calculate_time(time_control, self.board.unwrap().side_to_move());
-
-                let board = &self.board.unwrap();
-                let mut search = Search::new(&self.board.unwrap(), board.side_to_move());
-                let best_move = search.run(depth, None, None);
-                bestmove(best_move, None);
+                info!("UciMessage::Go {:?} {:?}", time_control, search_control);
+                self.stop_search();
+                let board = self.board.unwrap();
+                let search_control = search_control.unwrap_or_default();
+                let max_depth = max_depth(depth, &search_control);
+                let limits = search_limits(&time_control, search_control);
+                // Only go infinite waits for stop, other searches without a clock end by themselves
+                let infinite = time_control == Some(UciTimeControl::Infinite);
+                let move_time = calculate_time(time_control, board.side_to_move());
+                info!("Move time: {:?}, maximum depth: {}, limits: {:?}", move_time, max_depth, limits);
+                let played = self.played_since_last_search();
+                self.searched_position = self.position.clone();
+                let shared = Arc::new(SharedState::default());
+                shared.pondering.store(command.ponder, Ordering::Relaxed);
+                let (done, search_done) = mpsc::sync_channel(1);
+                let job = SearchJob {
+                    board,
+                    history: self.history.clone(),
+                    played,
+                    depth: max_depth,
+                    move_time,
+                    limits,
+                    infinite,
+                    parallel_mode: self.parallel_mode,
+                    table: Arc::clone(&self.table),
+                    threads: self.threads,
+                    options: self.search_options,
+                    shared: Arc::clone(&shared),
+                    done,
+                };
+                if self.search_jobs.send(job).is_ok() {
+                    self.search = Some((shared, search_done));
+                }
             }
             _ => {}
         }
 
         true
     }
+
+    /// The two moves played since the last search, our move and the reply, if the current
+    /// position continues the game of the last search that way.
+    fn played_since_last_search(&self) -> Option<Vec<ChessMove>> {
+        let (searched_fen, searched_moves) = self.searched_position.as_ref()?;
+        let (fen, moves) = self.position.as_ref()?;
+        if fen == searched_fen && moves.len() == searched_moves.len() + 2 && moves.starts_with(searched_moves) {
+            Some(moves[searched_moves.len()..].to_vec())
+        } else {
+            None
+        }
+    }
+
+    /// Stops the running search, and waits until its best move has been replied.
+    fn stop_search(&mut self) {
+        if let Some((shared, done)) = self.search.take() {
+            shared.stop.store(true, Ordering::Relaxed);
+            done.recv().ok();
+        }
+    }
+
+    fn set_option(&mut self, name: &str, value: Option<String>) {
+        info!("Setting option {} to {:?}", name, value);
+        let value = value.unwrap_or_default();
+        match name {
+            "Hash" => {
+                if let Ok(size_mb) = value.parse::<usize>() {
+                    self.table = Arc::new(TranspositionTable::new(size_mb.max(1)));
+                    // The kept tree would go on using the old table
+                    self.searched_position = None;
+                }
+            }
+            "Threads" => {
+                if let Ok(threads) = value.parse::<usize>() {
+                    self.threads = threads.clamp(1, MAX_THREADS);
+                }
+            }
+            "TreeMemory" => {
+                if let Ok(size_mb) = value.parse::<usize>() {
+                    self.set_tree_memory(size_mb);
+                }
+            }
+            "Contempt" => {
+                if let Ok(contempt) = value.parse::<i16>() {
+                    self.search_options.contempt = contempt.clamp(-MAX_CONTEMPT, MAX_CONTEMPT);
+                }
+            }
+            "NullMove" => {
+                if let Ok(null_move) = value.parse::<bool>() {
+                    self.search_options.null_move = null_move;
+                }
+            }
+            "LateMoveReductions" => {
+                if let Ok(late_move_reductions) = value.parse::<bool>() {
+                    self.search_options.late_move_reductions = late_move_reductions;
+                }
+            }
+            "FutilityPruning" => {
+                if let Ok(futility_pruning) = value.parse::<bool>() {
+                    self.search_options.futility_pruning = futility_pruning;
+                }
+            }
+            "ReverseFutilityPruning" => {
+                if let Ok(reverse_futility_pruning) = value.parse::<bool>() {
+                    self.search_options.reverse_futility_pruning = reverse_futility_pruning;
+                }
+            }
+            "Razoring" => {
+                if let Ok(razoring) = value.parse::<bool>() {
+                    self.search_options.razoring = razoring;
+                }
+            }
+            "LmrBase" => {
+                if let Ok(base) = value.parse::<u16>() {
+                    self.search_options.lmr_base = base.min(MAX_LMR_BASE);
+                }
+            }
+            "LmrDivisor" => {
+                if let Ok(divisor) = value.parse::<u16>() {
+                    self.search_options.lmr_divisor = divisor.clamp(MIN_LMR_DIVISOR, MAX_LMR_DIVISOR);
+                }
+            }
+            "ProbCut" => {
+                if let Ok(probcut) = value.parse::<bool>() {
+                    self.search_options.probcut = probcut;
+                }
+            }
+            "ProbCutMargin" => {
+                if let Ok(margin) = value.parse::<u16>() {
+                    self.search_options.probcut_margin = margin.min(MAX_PROBCUT_MARGIN);
+                }
+            }
+            "MultiCut" => {
+                if let Ok(multi_cut) = value.parse::<bool>() {
+                    self.search_options.multi_cut = multi_cut;
+                }
+            }
+            "ParallelMode" => {
+                if let Some(mode) = ParallelMode::from_name(&value) {
+                    self.parallel_mode = mode;
+                }
+            }
+            _ => info!("Unknown option {}", name),
+        }
+    }
+}
+
+/// Runs the searches one after another, replying with the best move of each, so that the
+/// engine keeps reading messages while thinking. Pondering and infinite searches reply only
+/// after ponderhit or stop, as the protocol requires.
+///
+/// The main search of the last job is kept, so that the next search can continue its tree.
+fn search_thread(jobs: Receiver<SearchJob>) {
+    let mut kept_search: Option<Search> = None;
+    for job in jobs {
+        let mut search = new_parallel_search(job.parallel_mode, &job.board, job.table, job.threads);
+        if let (Some(mut kept), Some(played)) = (kept_search.take(), job.played) {
+            if kept.reroot(&played) {
+                search.set_main_search(kept);
+            }
+        }
+        search.set_options(job.options);
+        search.set_limits(job.limits);
+        search.set_history(job.history);
+        search.set_info_output(true);
+        search.set_shared(Arc::clone(&job.shared));
+        let best_move = search.run_timed(job.depth, job.move_time);
+
+        let shared = job.shared;
+        let waiting = || {
+            (job.infinite || shared.pondering.load(Ordering::Relaxed)) && !shared.stop.load(Ordering::Relaxed)
+        };
+        while waiting() {
+            thread::sleep(PONDER_WAIT);
+        }
+        bestmove(best_move, search.ponder_move());
+        kept_search = search.take_main_search();
+        job.done.send(()).ok();
+    }
+}
+
+/// The board after the moves from the fen, or from the starting position, with the history
+/// of the game that led to it. The halfmove clock of the fen counts towards the fifty-move
+/// rule, the positions before the fen are unknown.
+fn game_position(fen: Option<&str>, moves: &[ChessMove]) -> (Board, GameHistory) {
+    let mut board = fen.map_or_else(Board::default, |fen| Board::from_str(fen).unwrap());
+    let mut history = GameHistory {
+        hashes: vec![],
+        halfmove_clock: fen.and_then(|fen| fen.split_whitespace().nth(4)?.parse().ok()).unwrap_or(0),
+    };
+    for mv in moves {
+        history = history.after(&board, *mv);
+        board = board.make_move_new(*mv);
+    }
+    (board, history)
+}
+
+/// The depth of go depth, or of go mate, where a mate in n moves takes 2n - 1 plies,
+/// otherwise the default depth.
+fn max_depth(default_depth: u8, search_control: &UciSearchControl) -> u8 {
+    let mut depth = search_control.depth.unwrap_or(default_depth);
+    if let Some(mate) = search_control.mate {
+        depth = depth.min((2 * mate as u16).saturating_sub(1) as u8);
+    }
+    depth.clamp(1, MAX_DEPTH)
+}
+
+fn search_limits(time_control: &Option<UciTimeControl>, search_control: UciSearchControl) -> SearchLimits {
+    let limited = search_control.depth.is_some() || search_control.nodes.is_some() || search_control.mate.is_some();
+    SearchLimits {
+        nodes: search_control.nodes,
+        mate: search_control.mate.is_some(),
+        // Without a clock, the search runs until stopped or until the depth, node or mate limit
+        infinite: matches!(time_control, Some(UciTimeControl::Infinite))
+            || (time_control.is_none() && limited),
+        search_moves: search_control.search_moves,
+    }
 }
 
 pub fn calculate_time(time_control: Option<UciTimeControl>, color: Color) -> Duration {
@@ -196,6 +476,79 @@ fn id() {
     });
 }
 
+fn options() {
+    reply(UciMessage::Option(UciOptionConfig::Spin {
+        name: "Hash".to_string(),
+        default: Some(DEFAULT_HASH_SIZE_MB as i64),
+        min: Some(1),
+        max: Some(MAX_HASH_SIZE_MB as i64),
+    }));
+    reply(UciMessage::Option(UciOptionConfig::Spin {
+        name: "Threads".to_string(),
+        default: Some(num_cpus::get() as i64),
+        min: Some(1),
+        max: Some(MAX_THREADS as i64),
+    }));
+    reply(UciMessage::Option(UciOptionConfig::Spin {
+        name: "TreeMemory".to_string(),
+        default: Some(DEFAULT_TREE_MEMORY_MB as i64),
+        min: Some(MIN_TREE_MEMORY_MB as i64),
+        max: Some(MAX_TREE_MEMORY_MB as i64),
+    }));
+    reply(UciMessage::Option(UciOptionConfig::Spin {
+        name: "Contempt".to_string(),
+        default: Some(0),
+        min: Some(-MAX_CONTEMPT as i64),
+        max: Some(MAX_CONTEMPT as i64),
+    }));
+    reply(UciMessage::Option(UciOptionConfig::Check {
+        name: "NullMove".to_string(),
+        default: Some(true),
+    }));
+    reply(UciMessage::Option(UciOptionConfig::Check {
+        name: "LateMoveReductions".to_string(),
+        default: Some(true),
+    }));
+    // Both in hundredths of a ply
+    reply(UciMessage::Option(UciOptionConfig::Spin {
+        name: "LmrBase".to_string(),
+        default: Some(DEFAULT_LMR_BASE as i64),
+        min: Some(0),
+        max: Some(MAX_LMR_BASE as i64),
+    }));
+    reply(UciMessage::Option(UciOptionConfig::Spin {
+        name: "LmrDivisor".to_string(),
+        default: Some(DEFAULT_LMR_DIVISOR as i64),
+        min: Some(MIN_LMR_DIVISOR as i64),
+        max: Some(MAX_LMR_DIVISOR as i64),
+    }));
+    for name in ["FutilityPruning", "ReverseFutilityPruning", "Razoring"] {
+        reply(UciMessage::Option(UciOptionConfig::Check {
+            name: name.to_string(),
+            default: Some(true),
+        }));
+    }
+    reply(UciMessage::Option(UciOptionConfig::Check {
+        name: "ProbCut".to_string(),
+        default: Some(true),
+    }));
+    reply(UciMessage::Option(UciOptionConfig::Spin {
+        name: "ProbCutMargin".to_string(),
+        default: Some(DEFAULT_PROBCUT_MARGIN as i64),
+        min: Some(0),
+        max: Some(MAX_PROBCUT_MARGIN as i64),
+    }));
+    reply(UciMessage::Option(UciOptionConfig::Check {
+        name: "MultiCut".to_string(),
+        default: Some(true),
+    }));
+    reply(UciMessage::Option(UciOptionConfig::Combo {
+        name: "ParallelMode".to_string(),
+        default: Some(ParallelMode::LazySmp.name().to_string()),
+        var: ParallelMode::ALL.iter().map(|mode| mode.name().to_string()).collect(),
+    }));
+}
+
 fn reply(message: UciMessage) {
     info!("tx: {:?}", message);
     println!("{}", message);
@@ -215,9 +568,12 @@ fn bestmove(best_move: ChessMove, ponder: Option<ChessMove>) {
 
 #[cfg(test)]
 mod tests {
-    use crate::engine::calculate_time;
-    use chess::Color;
-    use std::time::Duration;
+    use crate::engine::{calculate_time, game_position, max_depth, parse_command, search_limits, Engine};
+    use chess::{Board, ChessMove, Color};
+    use std::str::FromStr;
+    use std::thread;
+    use std::time::{Duration, Instant};
+    use vampirc_uci::{UciMessage, UciSearchControl};
     use vampirc_uci::Duration as VampDuration;
     use vampirc_uci::UciTimeControl;
 
@@ -255,4 +611,116 @@ mod tests {
             Duration::from_millis(120)
         )
     }
+
+    #[test]
+    fn test_game_position_keeps_the_history() {
+        let moves: Vec<ChessMove> = ["e2e4", "e7e5", "g1f3", "g8f6"].iter().map(|mv| mv.parse().unwrap()).collect();
+        let (board, history) = game_position(None, &moves);
+        assert_eq!(board, Board::from_str("rnbqkb1r/pppp1ppp/5n2/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3").unwrap());
+        assert_eq!(history.hashes.len(), 2);
+        assert_eq!(history.halfmove_clock, 2);
+
+        let (_, history) = game_position(Some("6k1/8/8/8/8/8/8/6K1 w - - 37 80"), &["g1h1".parse().unwrap()]);
+        assert_eq!(history.halfmove_clock, 38);
+    }
+
+    #[test]
+    fn test_stopping_a_running_search() {
+        let mut engine = Engine::default();
+        engine.handle_command(parse_command("position startpos moves e2e4"), 64);
+        let start = Instant::now();
+        engine.handle_command(parse_command("go movetime 60000"), 64);
+        assert!(engine.search.is_some());
+
+        assert!(engine.handle_command(parse_command("isready"), 64));
+        engine.handle_command(parse_command("stop"), 64);
+        assert!(engine.search.is_none());
+        assert!(!engine.handle_command(parse_command("quit"), 64));
+        assert!(start.elapsed() < Duration::from_secs(30));
+    }
+
+    #[test]
+    fn test_parsing_go_ponder_with_clock() {
+        let command = parse_command("go ponder wtime 1000 btime 1000");
+        assert!(command.ponder);
+        assert!(matches!(command.message, UciMessage::Go { time_control: Some(UciTimeControl::TimeLeft { .. }), .. }));
+        assert!(parse_command("go ponder").ponder);
+        assert!(!parse_command("go wtime 1000 btime 1000").ponder);
+    }
+
+    #[test]
+    fn test_pondering_until_ponderhit() {
+        let mut engine = Engine::default();
+        engine.handle_command(parse_command("position startpos moves e2e4 e7e5"), 64);
+        engine.handle_command(parse_command("go ponder movetime 100"), 64);
+        thread::sleep(Duration::from_millis(500));
+        assert!(engine.search.as_ref().unwrap().1.try_recv().is_err());
+
+        engine.handle_command(parse_command("ponderhit"), 64);
+        wait_for_best_move(&mut engine);
+    }
+
+    #[test]
+    fn test_search_controls() {
+        let control = |line: &str| match parse_command(line).message {
+            UciMessage::Go {
+                time_control,
+                search_control,
+            } => (time_control, search_control.unwrap_or_default()),
+            _ => unreachable!(),
+        };
+        let (time_control, search_control) = control("go depth 5");
+        assert_eq!(max_depth(64, &search_control), 5);
+        assert!(search_limits(&time_control, search_control).infinite);
+
+        let (time_control, search_control) = control("go mate 2 wtime 1000 btime 1000");
+        assert_eq!(max_depth(64, &search_control), 3);
+        let limits = search_limits(&time_control, search_control);
+        assert!(limits.mate);
+        assert!(!limits.infinite);
+
+        let (time_control, search_control) = control("go nodes 1000 searchmoves e2e4 d2d4");
+        let limits = search_limits(&time_control, search_control);
+        assert_eq!(limits.nodes, Some(1000));
+        assert_eq!(limits.search_moves.len(), 2);
+
+        assert_eq!(max_depth(7, &UciSearchControl::default()), 7);
+    }
+
+    #[test]
+    fn test_infinite_search_waits_for_stop() {
+        let mut engine = Engine::default();
+        engine.handle_command(parse_command("position fen 7k/7P/6PK/8/8/8/8/8 w - - 0 1"), 3);
+        engine.handle_command(parse_command("go infinite"), 3);
+        thread::sleep(Duration::from_millis(500));
+        assert!(engine.search.as_ref().unwrap().1.try_recv().is_err());
+        engine.handle_command(parse_command("stop"), 3);
+
+        engine.handle_command(parse_command("go depth 2"), 64);
+        wait_for_best_move(&mut engine);
+    }
+
+    fn wait_for_best_move(engine: &mut Engine) {
+        let (_, done) = engine.search.take().unwrap();
+        assert!(done.recv_timeout(Duration::from_secs(30)).is_ok());
+    }
+
+    #[test]
+    fn test_search_continues_after_expected_two_plies() {
+        let mut engine = Engine::default();
+        engine.handle_command(parse_command("position startpos moves e2e4"), 64);
+        engine.handle_command(parse_command("go depth 3"), 64);
+        wait_for_best_move(&mut engine);
+
+        engine.handle_command(parse_command("position startpos moves e2e4 e7e5 g1f3"), 64);
+        assert_eq!(engine.played_since_last_search().unwrap().len(), 2);
+        engine.handle_command(parse_command("position startpos moves d2d4 e7e5 g1f3"), 64);
+        assert!(engine.played_since_last_search().is_none());
+        engine.handle_command(parse_command("position startpos moves e2e4 e7e5"), 64);
+        assert!(engine.played_since_last_search().is_none());
+
+        engine.handle_command(parse_command("position startpos moves e2e4 e7e5 g1f3"), 64);
+        engine.handle_command(parse_command("go depth 3"), 64);
+        wait_for_best_move(&mut engine);
+    }
 }
@@ -0,0 +1,172 @@
+use chess::{Board, ChessMove, MoveGen, Piece};
+
+use crate::engine::eval::piece_value;
+use crate::engine::see::see;
+
+const HASH_MOVE_SCORE: i32 = 1_000_000;
+const CAPTURE_SCORE: i32 = 100_000;
+const KILLER_SCORE: i32 = 90_000;
+const MAX_HISTORY_SCORE: i32 = 80_000;
+
+/// Decides in which order the moves of a position are searched, so that alpha-beta
+/// finds the cutoffs as early as possible.
+///
+/// The order is: hash (or principal variation) move, captures that do not lose material by
+/// most valuable victim and least valuable attacker, killer moves of the same ply, the quiet
+/// moves by their history heuristic score, and finally the losing captures by how much their
+/// static exchange evaluation loses.
+pub struct MoveOrdering {
+    killers: Vec<[Option<ChessMove>; 2]>,
+    history: Vec<[i32; 64]>,
+}
+
+impl Default for MoveOrdering {
+    fn default() -> Self {
+        MoveOrdering {
+            killers: vec![],
+            history: vec![[0; 64]; 64],
+        }
+    }
+}
+
+impl MoveOrdering {
+    /// Returns the legal moves of the board, with the move to be searched first at the end,
+    /// so that they can be popped.
+    pub fn ordered_moves(&self, board: &Board, hash_move: Option<ChessMove>, ply: usize) -> Vec<ChessMove> {
+        let mut moves: Vec<(i32, ChessMove)> = MoveGen::new_legal(board)
+            .map(|mv| (self.score(board, mv, hash_move, ply), mv))
+            .collect();
+        moves.sort_by_key(|(score, _)| *score);
+        moves.into_iter().map(|(_, mv)| mv).collect()
+    }
+
+    fn score(&self, board: &Board, mv: ChessMove, hash_move: Option<ChessMove>, ply: usize) -> i32 {
+        if Some(mv) == hash_move {
+            HASH_MOVE_SCORE
+        } else if is_capture(board, &mv) || mv.get_promotion().is_some() {
+            match see(board, mv) {
+                exchange if exchange < 0 => exchange as i32,
+                _ => CAPTURE_SCORE + mvv_lva(board, &mv),
+            }
+        } else if let Some(position) = self.killers(ply).iter().position(|killer| *killer == Some(mv)) {
+            KILLER_SCORE - position as i32
+        } else {
+            self.history[mv.get_source().to_index()][mv.get_dest().to_index()]
+        }
+    }
+
+    /// Remembers a quiet move that caused a beta cutoff.
+    pub fn add_cutoff(&mut self, board: &Board, mv: ChessMove, ply: usize, depth_left: u8) {
+        if is_capture(board, &mv) || mv.get_promotion().is_some() {
+            return;
+        }
+        if self.killers.len() <= ply {
+            self.killers.resize(ply + 1, [None; 2]);
+        }
+        let killers = &mut self.killers[ply];
+        if killers[0] != Some(mv) {
+            killers[1] = killers[0];
+            killers[0] = Some(mv);
+        }
+
+        let history = &mut self.history[mv.get_source().to_index()][mv.get_dest().to_index()];
+        *history += depth_left as i32 * depth_left as i32;
+        if *history > MAX_HISTORY_SCORE {
+            // Halving everything keeps the relative order, while leaving room for new cutoffs
+            self.history
+                .iter_mut()
+                .for_each(|scores| scores.iter_mut().for_each(|score| *score /= 2));
+        }
+    }
+
+    /// Moves the killers of every ply closer to the root, for a search that continues
+    /// from a position the given number of plies further in the game.
+    pub fn advance(&mut self, plies: usize) {
+        self.killers.drain(..plies.min(self.killers.len()));
+    }
+
+    fn killers(&self, ply: usize) -> [Option<ChessMove>; 2] {
+        self.killers.get(ply).copied().unwrap_or([None; 2])
+    }
+}
+
+pub fn is_capture(board: &Board, mv: &ChessMove) -> bool {
+    board.piece_on(mv.get_dest()).is_some()
+        || (board.piece_on(mv.get_source()) == Some(Piece::Pawn)
+            && mv.get_source().get_file() != mv.get_dest().get_file())
+}
+
+/// Most valuable victim, least valuable attacker score of a capture.
+pub fn mvv_lva(board: &Board, mv: &ChessMove) -> i32 {
+    let victim = match board.piece_on(mv.get_dest()) {
+        Some(piece) => piece_value(piece),
+        // en passant
+        None if is_capture(board, mv) => piece_value(Piece::Pawn),
+        None => 0,
+    };
+    let promotion = mv.get_promotion().map_or(0, piece_value);
+    let attacker = board.piece_on(mv.get_source()).map_or(0, piece_value);
+    10 * (victim + promotion) as i32 - attacker as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chess::Square;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_hash_move_goes_first_then_captures() {
+        let board = Board::from_str("4k3/8/8/3p4/8/2N5/8/4K3 w - - 0 1").unwrap();
+        let ordering = MoveOrdering::default();
+        let hash_move = ChessMove::new(Square::E1, Square::F2, None);
+        let moves = ordering.ordered_moves(&board, Some(hash_move), 0);
+        assert_eq!(moves[moves.len() - 1], hash_move);
+        assert_eq!(moves[moves.len() - 2], ChessMove::new(Square::C3, Square::D5, None));
+    }
+
+    #[test]
+    fn test_most_valuable_victim_is_captured_first() {
+        let board = Board::from_str("4k3/8/8/2r1p3/3P4/8/8/4K3 w - - 0 1").unwrap();
+        let ordering = MoveOrdering::default();
+        let moves = ordering.ordered_moves(&board, None, 0);
+        assert_eq!(moves[moves.len() - 1], ChessMove::new(Square::D4, Square::C5, None));
+        assert_eq!(moves[moves.len() - 2], ChessMove::new(Square::D4, Square::E5, None));
+    }
+
+    #[test]
+    fn test_losing_captures_go_last() {
+        // Nxb5 wins a pawn, while the pawn on e6 takes back on d5, worst after Qxd5
+        let board = Board::from_str("4k3/8/4p3/1p1p4/8/2N5/8/3QK3 w - - 0 1").unwrap();
+        let ordering = MoveOrdering::default();
+        let moves = ordering.ordered_moves(&board, None, 0);
+        assert_eq!(moves[moves.len() - 1], ChessMove::new(Square::C3, Square::B5, None));
+        assert_eq!(moves[1], ChessMove::new(Square::C3, Square::D5, None));
+        assert_eq!(moves[0], ChessMove::new(Square::D1, Square::D5, None));
+    }
+
+    #[test]
+    fn test_killers_and_history_order_quiet_moves() {
+        let board = Board::from_str("4k3/8/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let mut ordering = MoveOrdering::default();
+        let killer = ChessMove::new(Square::E1, Square::D1, None);
+        let history = ChessMove::new(Square::E1, Square::F1, None);
+        ordering.add_cutoff(&board, history, 3, 4);
+        ordering.add_cutoff(&board, killer, 2, 1);
+
+        let moves = ordering.ordered_moves(&board, None, 2);
+        assert_eq!(moves[moves.len() - 1], killer);
+        assert_eq!(moves[moves.len() - 2], history);
+    }
+
+    #[test]
+    fn test_advancing_keeps_killers_of_later_plies() {
+        let board = Board::from_str("4k3/8/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let mut ordering = MoveOrdering::default();
+        let killer = ChessMove::new(Square::E1, Square::D1, None);
+        ordering.add_cutoff(&board, killer, 3, 1);
+        ordering.advance(2);
+        assert_eq!(ordering.killers(1)[0], Some(killer));
+        assert_eq!(ordering.killers(3)[0], None);
+    }
+}
@@ -0,0 +1,553 @@
+use chess::{Board, ChessMove, MoveGen, EMPTY};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use vampirc_uci::Duration as VampDuration;
+use vampirc_uci::{UciInfoAttribute, UciMessage};
+
+use super::reply;
+use crate::engine::eval::{eval, mate_score_from_root, mate_score_to_root, MATE_THRESHOLD};
+use crate::engine::ordering::MoveOrdering;
+use crate::engine::search::{
+    chess_move_to_string, extend_from_table, uci_score, GameHistory, Search, SearchLimits, SearchOptions,
+    SharedState, INFINITY,
+};
+use crate::engine::transposition::TranspositionTable;
+
+#[cfg(not(test))]
+use log::info;
+
+#[cfg(test)]
+use std::println as info;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParallelMode {
+    LazySmp,
+    RootSplitting,
+}
+
+impl ParallelMode {
+    pub const ALL: [ParallelMode; 2] = [ParallelMode::LazySmp, ParallelMode::RootSplitting];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ParallelMode::LazySmp => "LazySMP",
+            ParallelMode::RootSplitting => "RootSplitting",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<ParallelMode> {
+        ParallelMode::ALL.into_iter().find(|mode| mode.name() == name)
+    }
+}
+
+/// A search of one position using several threads.
+pub trait ParallelSearch {
+    fn set_options(&mut self, options: SearchOptions);
+    fn set_info_output(&mut self, info_output: bool);
+    /// Shares the stop flag and node counter with the caller, so that the search can be
+    /// stopped from another thread. A stopped search returns the best move found so far.
+    fn set_shared(&mut self, shared: Arc<SharedState>);
+    fn set_limits(&mut self, limits: SearchLimits);
+    /// Sets the game that led to the position, for repetitions and the fifty-move rule.
+    fn set_history(&mut self, history: GameHistory);
+    /// Nodes searched by all threads so far.
+    fn nodes(&self) -> u64;
+    /// The expected reply to the best move of the last search, taken from its principal
+    /// variation.
+    fn ponder_move(&self) -> Option<ChessMove>;
+    fn run_timed(&mut self, max_depth: u8, move_time: Duration) -> ChessMove;
+    /// Hands over a search of the same position, so that its tree is searched further.
+    fn set_main_search(&mut self, _search: Search) {}
+    /// Takes the search whose tree is worth keeping for the next move, if there is one.
+    fn take_main_search(&mut self) -> Option<Search> {
+        None
+    }
+}
+
+pub fn new_parallel_search(
+    mode: ParallelMode,
+    board: &Board,
+    table: Arc<TranspositionTable>,
+    threads: usize,
+) -> Box<dyn ParallelSearch> {
+    match mode {
+        ParallelMode::LazySmp => Box::new(LazySmp::new(board, table, threads)),
+        ParallelMode::RootSplitting => Box::new(RootSplitting::new(board, table, threads)),
+    }
+}
+
+/// Lazy SMP: every thread runs its own iterative deepening search of the same position,
+/// and the threads only cooperate through the shared transposition table. The main thread
+/// decides the move, helper threads fill the table with results the main thread can use.
+pub struct LazySmp {
+    board: Board,
+    table: Arc<TranspositionTable>,
+    threads: usize,
+    options: SearchOptions,
+    limits: SearchLimits,
+    history: GameHistory,
+    info_output: bool,
+    shared: Arc<SharedState>,
+    ponder_move: Option<ChessMove>,
+    main_search: Option<Search>,
+}
+
+impl LazySmp {
+    pub fn new(board: &Board, table: Arc<TranspositionTable>, threads: usize) -> LazySmp {
+        LazySmp {
+            board: *board,
+            table,
+            threads: threads.max(1),
+            options: SearchOptions::default(),
+            limits: SearchLimits::default(),
+            history: GameHistory::default(),
+            info_output: false,
+            shared: Arc::new(SharedState::default()),
+            ponder_move: None,
+            main_search: None,
+        }
+    }
+}
+
+impl ParallelSearch for LazySmp {
+    fn set_options(&mut self, options: SearchOptions) {
+        self.options = options;
+    }
+
+    fn set_info_output(&mut self, info_output: bool) {
+        self.info_output = info_output;
+    }
+
+    fn set_shared(&mut self, shared: Arc<SharedState>) {
+        self.shared = shared;
+    }
+
+    fn set_limits(&mut self, limits: SearchLimits) {
+        self.limits = limits;
+    }
+
+    fn set_history(&mut self, history: GameHistory) {
+        self.history = history;
+    }
+
+    fn nodes(&self) -> u64 {
+        self.shared.nodes.load(Ordering::Relaxed)
+    }
+
+    fn ponder_move(&self) -> Option<ChessMove> {
+        self.ponder_move
+    }
+
+    fn set_main_search(&mut self, search: Search) {
+        self.main_search = Some(search);
+    }
+
+    fn take_main_search(&mut self) -> Option<Search> {
+        self.main_search.take()
+    }
+
+    fn run_timed(&mut self, max_depth: u8, move_time: Duration) -> ChessMove {
+        info!("Starting Lazy SMP search with {} threads", self.threads);
+        self.shared.abort.store(false, Ordering::Relaxed);
+        let helpers: Vec<_> = (1..self.threads)
+            .map(|helper_id| {
+                let board = self.board;
+                let table = Arc::clone(&self.table);
+                let shared = Arc::clone(&self.shared);
+                let options = self.options.per_thread(self.threads);
+                let limits = self.limits.clone();
+                let history = self.history.clone();
+                thread::spawn(move || {
+                    let mut search = Search::with_table(&board, board.side_to_move(), table);
+                    search.set_options(options);
+                    search.set_limits(limits);
+                    search.set_history(&history);
+                    search.set_shared(shared);
+                    search.set_depth_offset((helper_id % 2) as u8);
+                    search.run_timed(max_depth, move_time);
+                })
+            })
+            .collect();
+
+        let mut search = self.main_search.take().unwrap_or_else(|| {
+            Search::with_table(&self.board, self.board.side_to_move(), Arc::clone(&self.table))
+        });
+        search.set_options(self.options.per_thread(self.threads));
+        search.set_limits(self.limits.clone());
+        search.set_history(&self.history);
+        search.set_shared(Arc::clone(&self.shared));
+        search.set_info_output(self.info_output);
+        let best_move = search.run_timed(max_depth, move_time);
+        let pv = search.principal_variation();
+        self.ponder_move = pv.get(1).copied().filter(|_| pv[0] == best_move);
+        self.main_search = Some(search);
+
+        self.shared.abort.store(true, Ordering::Relaxed);
+        for helper in helpers {
+            helper.join().ok();
+        }
+        best_move
+    }
+}
+
+/// Root splitting, as described in docs/design.md: the first two plies are expanded into
+/// a forest of subroots, which are put on a work queue. Every thread takes one subroot at
+/// a time from the queue and searches it, with the alpha of the root shared by all of them.
+pub struct RootSplitting {
+    board: Board,
+    table: Arc<TranspositionTable>,
+    threads: usize,
+    options: SearchOptions,
+    limits: SearchLimits,
+    history: GameHistory,
+    info_output: bool,
+    shared: Arc<SharedState>,
+    move_time: Duration,
+    ponder_move: Option<ChessMove>,
+}
+
+struct RootMove {
+    chess_move: ChessMove,
+    // Minimum over the replies searched so far, from the point of view of the root
+    value: i16,
+    pending_replies: usize,
+}
+
+struct RootState {
+    alpha: i16,
+    best: Option<usize>,
+    moves: Vec<RootMove>,
+    deadline: Option<Instant>,
+    // Deepest ply from the root reached by any subroot search
+    seldepth: u8,
+}
+
+struct Subroot {
+    root_move: usize,
+    board: Board,
+    // The forest plies are game history for the search of the subroot
+    history: GameHistory,
+}
+
+impl RootSplitting {
+    pub fn new(board: &Board, table: Arc<TranspositionTable>, threads: usize) -> RootSplitting {
+        RootSplitting {
+            board: *board,
+            table,
+            threads: threads.max(1),
+            options: SearchOptions::default(),
+            limits: SearchLimits::default(),
+            history: GameHistory::default(),
+            info_output: false,
+            shared: Arc::new(SharedState::default()),
+            move_time: Duration::ZERO,
+            ponder_move: None,
+        }
+    }
+
+    /// Searches all subroots to `depth` - 2 plies and returns the best root move with its
+    /// score and the selective depth, or `None` if the deadline came first. While
+    /// pondering, the deadline is kept a move time ahead.
+    fn run_iteration(
+        &self,
+        depth: u8,
+        previous_best: Option<ChessMove>,
+        deadline: &mut Option<Instant>,
+    ) -> Option<(ChessMove, i16, u8)> {
+        let mut root_moves = MoveOrdering::default().ordered_moves(&self.board, previous_best, 0);
+        let search_moves = &self.limits.search_moves;
+        if root_moves.iter().any(|mv| search_moves.contains(mv)) {
+            root_moves.retain(|mv| search_moves.contains(mv));
+        }
+        let mut moves = vec![];
+        let mut subroots = vec![];
+        for (root_move, chess_move) in root_moves.into_iter().rev().enumerate() {
+            let board = self.board.make_move_new(chess_move);
+            let history = self.history.after(&self.board, chess_move);
+            let replies: Vec<ChessMove> = MoveGen::new_legal(&board).collect();
+            // Checkmate, stalemate and draws are scored right away
+            let (value, replies) = if replies.is_empty() && *board.checkers() != EMPTY {
+                (mate_score_to_root(eval(&board, &[], self.board.side_to_move()), 1), replies)
+            } else if replies.is_empty() || history.is_draw(&board) {
+                (-self.options.contempt, vec![])
+            } else {
+                (INFINITY, replies)
+            };
+            moves.push(RootMove {
+                chess_move,
+                value,
+                pending_replies: replies.len(),
+            });
+            subroots.extend(replies.into_iter().map(|reply| Subroot {
+                root_move,
+                board: board.make_move_new(reply),
+                history: history.after(&board, reply),
+            }));
+        }
+        let (alpha, best) = moves
+            .iter()
+            .enumerate()
+            .filter(|(_, root_move)| root_move.pending_replies == 0)
+            .map(|(i, root_move)| (root_move.value, Some(i)))
+            .max()
+            .unwrap_or((-INFINITY, None));
+        let state = Mutex::new(RootState {
+            alpha,
+            best,
+            moves,
+            deadline: *deadline,
+            seldepth: depth,
+        });
+        let next_subroot = AtomicUsize::new(0);
+
+        thread::scope(|scope| {
+            for _ in 0..self.threads {
+                scope.spawn(|| self.work(&subroots, &next_subroot, &state, depth - 2));
+            }
+        });
+
+        let state = state.into_inner().unwrap();
+        *deadline = state.deadline;
+        if self.shared.is_stopped() {
+            return None;
+        }
+        state.best.map(|best| (state.moves[best].chess_move, state.alpha, state.seldepth))
+    }
+
+    fn work(
+        &self,
+        subroots: &[Subroot],
+        next_subroot: &AtomicUsize,
+        state: &Mutex<RootState>,
+        depth: u8,
+    ) {
+        loop {
+            let i = next_subroot.fetch_add(1, Ordering::Relaxed);
+            if i >= subroots.len() || self.shared.is_stopped() {
+                return;
+            }
+            let subroot = &subroots[i];
+            let (alpha, beta, deadline) = {
+                let mut state = state.lock().unwrap();
+                if self.shared.pondering.load(Ordering::Relaxed) {
+                    state.deadline = state.deadline.map(|_| Instant::now() + self.move_time);
+                }
+                (state.alpha, state.moves[subroot.root_move].value, state.deadline)
+            };
+
+            // A root move already refuted by one reply needs no more of them searched
+            let score = if beta <= alpha {
+                beta
+            } else {
+                let mut search = Search::with_table(&subroot.board, self.board.side_to_move(), Arc::clone(&self.table));
+                search.set_options(self.options.per_thread(self.threads));
+                search.set_history(&subroot.history);
+                search.set_shared(Arc::clone(&self.shared));
+                // The root moves to search are already chosen, only the node limit applies
+                search.set_limits(SearchLimits {
+                    nodes: self.limits.nodes,
+                    ..SearchLimits::default()
+                });
+                search.set_deadline(deadline);
+                // Subroots are two plies below the root, where mates are two plies closer
+                match search.score(depth, mate_score_from_root(alpha, 2), mate_score_from_root(beta, 2)) {
+                    Some(score) => {
+                        let mut state = state.lock().unwrap();
+                        state.seldepth = state.seldepth.max(search.seldepth() + 2);
+                        mate_score_to_root(score, 2)
+                    }
+                    None => {
+                        self.shared.abort.store(true, Ordering::Relaxed);
+                        return;
+                    }
+                }
+            };
+
+            let mut state = state.lock().unwrap();
+            let root_move = &mut state.moves[subroot.root_move];
+            root_move.value = root_move.value.min(score);
+            root_move.pending_replies -= 1;
+            if root_move.pending_replies == 0 && root_move.value > state.alpha {
+                state.alpha = state.moves[subroot.root_move].value;
+                state.best = Some(subroot.root_move);
+            }
+        }
+    }
+}
+
+impl ParallelSearch for RootSplitting {
+    fn set_options(&mut self, options: SearchOptions) {
+        self.options = options;
+    }
+
+    fn set_info_output(&mut self, info_output: bool) {
+        self.info_output = info_output;
+    }
+
+    fn set_shared(&mut self, shared: Arc<SharedState>) {
+        self.shared = shared;
+    }
+
+    fn set_limits(&mut self, limits: SearchLimits) {
+        self.limits = limits;
+    }
+
+    fn set_history(&mut self, history: GameHistory) {
+        self.history = history;
+    }
+
+    fn nodes(&self) -> u64 {
+        self.shared.nodes.load(Ordering::Relaxed)
+    }
+
+    fn ponder_move(&self) -> Option<ChessMove> {
+        self.ponder_move
+    }
+
+    fn run_timed(&mut self, max_depth: u8, move_time: Duration) -> ChessMove {
+        info!("Starting root splitting search with {} threads", self.threads);
+        self.shared.abort.store(false, Ordering::Relaxed);
+        let start = Instant::now();
+        let mut deadline = if self.limits.infinite {
+            None
+        } else {
+            Some(start + move_time)
+        };
+        self.move_time = move_time;
+
+        // The first two plies are the forest itself, so they are searched by a single thread,
+        // which stops and counts its nodes with the others
+        let mut search = Search::with_table(&self.board, self.board.side_to_move(), Arc::clone(&self.table));
+        search.set_options(self.options);
+        search.set_limits(self.limits.clone());
+        search.set_history(&self.history);
+        search.set_shared(Arc::clone(&self.shared));
+        search.set_info_output(self.info_output);
+        let mut best_move = search.run_timed(max_depth.min(2), move_time);
+        search.count_remaining_nodes();
+        let mut pv = search.principal_variation();
+
+        for depth in 3..=max_depth {
+            if self.shared.pondering.load(Ordering::Relaxed) {
+                deadline = deadline.map(|_| Instant::now() + move_time);
+            }
+            match self.run_iteration(depth, Some(best_move), &mut deadline) {
+                Some((chess_move, score, seldepth)) => {
+                    best_move = chess_move;
+                    pv = vec![best_move];
+                    extend_from_table(&self.table, self.board.make_move_new(best_move), &mut pv);
+                    info!(
+                        "Finished depth {} in {:?}, best move: {}",
+                        depth,
+                        start.elapsed(),
+                        chess_move_to_string(&best_move)
+                    );
+                    if self.info_output {
+                        let elapsed = start.elapsed().as_millis() as u64;
+                        reply(UciMessage::Info(vec![
+                            UciInfoAttribute::Depth(depth),
+                            UciInfoAttribute::SelDepth(seldepth),
+                            uci_score(score),
+                            UciInfoAttribute::Nodes(self.nodes()),
+                            UciInfoAttribute::Nps(self.nodes() * 1000 / elapsed.max(1)),
+                            UciInfoAttribute::Time(VampDuration::milliseconds(elapsed as i64)),
+                            UciInfoAttribute::HashFull(self.table.hashfull()),
+                            UciInfoAttribute::Pv(pv.clone()),
+                        ]));
+                    }
+                    if self.limits.mate && score >= MATE_THRESHOLD {
+                        break;
+                    }
+                }
+                None => {
+                    info!("Search aborted at depth {}, after {:?}", depth, start.elapsed());
+                    break;
+                }
+            }
+            let pondering = self.shared.pondering.load(Ordering::Relaxed);
+            let time_left = deadline.map_or(Duration::MAX, |deadline| deadline.saturating_duration_since(Instant::now()));
+            if !pondering && time_left <= move_time / 2 {
+                break;
+            }
+        }
+        self.ponder_move = pv.get(1).copied();
+        best_move
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::eval::mated_in;
+    use chess::BoardStatus;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_helpers_share_the_work() {
+        let board = Board::from_str("r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4").unwrap();
+        let mut smp = LazySmp::new(&board, Arc::new(TranspositionTable::new(4)), 3);
+        let best = smp.run_timed(4, Duration::from_secs(60));
+        assert!(board.legal(best));
+        assert!(smp.nodes() > 0);
+    }
+
+    #[test]
+    fn test_finding_checkmate_with_helpers() {
+        let board = Board::from_str("7k/7P/6PK/8/8/8/8/8 w - - 0 1").unwrap();
+        let mut smp = LazySmp::new(&board, Arc::new(TranspositionTable::new(1)), 4);
+        let best = smp.run_timed(3, Duration::from_secs(60));
+        assert_eq!(board.make_move_new(best).status(), BoardStatus::Checkmate);
+    }
+
+    #[test]
+    fn test_root_splitting_agrees_with_single_thread_search() {
+        for fen in [
+            "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4",
+            "4k3/8/4p3/3p4/8/8/8/3QK3 w - - 0 1",
+            "2r2rk1/p1qnbppp/1p1ppn2/6N1/2PQ4/2N3P1/PP2PPKP/R1B2R2 w - - 3 14",
+        ] {
+            let board = Board::from_str(fen).unwrap();
+            let splitting = RootSplitting::new(&board, Arc::new(TranspositionTable::new(4)), 3);
+            let (_, split_score, seldepth) = splitting.run_iteration(4, None, &mut None).unwrap();
+            assert!(seldepth >= 4);
+
+            let mut search = Search::with_table(&board, board.side_to_move(), Arc::new(TranspositionTable::new(4)));
+            assert_eq!(search.score(4, -INFINITY, INFINITY), Some(split_score));
+        }
+    }
+
+    #[test]
+    fn test_root_splitting_counts_the_nodes_of_the_first_plies() {
+        let board = Board::from_str("r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4").unwrap();
+        let mut splitting = RootSplitting::new(&board, Arc::new(TranspositionTable::new(1)), 2);
+        splitting.run_timed(2, Duration::from_secs(60));
+        let mut search = Search::with_table(&board, board.side_to_move(), Arc::new(TranspositionTable::new(1)));
+        search.run_timed(2, Duration::from_secs(60));
+        assert_eq!(splitting.nodes(), search.statistics().nodes);
+    }
+
+    #[test]
+    fn test_root_splitting_repeats_for_a_draw() {
+        let board = Board::from_str("6k1/2q5/8/8/8/8/8/6K1 w - - 5 10").unwrap();
+        let repeated = board.make_move_new(ChessMove::from_str("g1h1").unwrap()).get_hash();
+        let mut splitting = RootSplitting::new(&board, Arc::new(TranspositionTable::new(1)), 2);
+        splitting.set_history(GameHistory {
+            hashes: vec![repeated, 1, repeated, 2, 3],
+            halfmove_clock: 5,
+        });
+        let best = splitting.run_timed(3, Duration::from_secs(60));
+        assert_eq!(best.to_string(), "g1h1");
+    }
+
+    #[test]
+    fn test_root_splitting_finds_checkmate() {
+        let board = Board::from_str("4r2k/4p3/1p6/q1p5/1Pp5/pPP2pPp/P4P1P/7K b - - 0 1").unwrap();
+        let mut splitting = RootSplitting::new(&board, Arc::new(TranspositionTable::new(1)), 2);
+        let best = splitting.run_timed(3, Duration::from_secs(60));
+        let mut search = Search::with_table(&board.make_move_new(best), board.side_to_move(), Arc::new(TranspositionTable::new(1)));
+        // Mated with the second ply from there
+        assert_eq!(search.score(2, -INFINITY, INFINITY), Some(mated_in(2)));
+    }
+}
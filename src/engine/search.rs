@@ -1,17 +1,110 @@
-use chess::{Board, ChessMove, Color, Game, MoveGen, Piece, Square};
+use chess::{Board, ChessMove, Color, MoveGen, Piece, EMPTY};
+use minimax::hooks::{Hooks, Probe, Prune, Visit, Window};
+use minimax::Observer;
 
+use super::reply;
 use super::tree::Tree;
-use crate::engine::eval::{eval, eval_with_children};
+use crate::engine::eval::{
+    draw_score, eval, is_mate_score, mate_in_moves, mate_score_from_root, mate_score_to_root, non_pawn_material,
+    MATE_THRESHOLD,
+};
+use crate::engine::game::ChessGame;
+use crate::engine::ordering::{is_capture, mvv_lva, MoveOrdering};
+use crate::engine::see::see;
+use crate::engine::transposition::{Bound, Entry, TranspositionTable};
 use crate::engine::utils::show_board;
-use std::cmp::{max, min};
-use std::rc::{Rc, Weak};
-use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use vampirc_uci::Duration as VampDuration;
+use vampirc_uci::{UciInfoAttribute, UciMessage};
+
+#[cfg(not(test))]
+use log::info;
 
-#[cfg(not(test))] 
-use log::{info, warn};
- 
 #[cfg(test)]
-use std::{println as info, println as warn};
+use std::println as info;
+
+pub const MAX_DEPTH: u8 = 64;
+pub const INFINITY: i16 = i16::MAX;
+// How many nodes are visited between two checks of the clock
+const NODES_BETWEEN_TIME_CHECKS: u64 = 1024;
+const INFO_INTERVAL: Duration = Duration::from_secs(1);
+pub const DEFAULT_TREE_MEMORY_MB: usize = 256;
+// Keeps room for the path being searched and the children along it, which are never evicted
+pub const MIN_TREE_MEMORY_MB: usize = 4;
+pub const MAX_TREE_MEMORY_MB: usize = 65536;
+pub const MAX_CONTEMPT: i16 = 100;
+// Plies without captures or pawn moves after which the game is drawn
+const FIFTY_MOVES: u16 = 100;
+// Null moves are searched this many plies shallower than other moves, and a ply more for
+// every NULL_MOVE_DEPTH_STEP plies of depth left
+const NULL_MOVE_REDUCTION: u8 = 2;
+const NULL_MOVE_DEPTH_STEP: u8 = 4;
+const NULL_MOVE_MIN_DEPTH: u8 = 3;
+// With at most about a rook and a minor piece zugzwang is likely enough that null move
+// cutoffs are verified by a search a ply shallower, that does not pass at the same node
+const NULL_MOVE_VERIFICATION_MATERIAL: i16 = 800;
+// Late move reductions start at this depth left, and after this many moves searched in full
+const LMR_MIN_DEPTH: u8 = 3;
+const LMR_FULL_DEPTH_MOVES: usize = 3;
+// Near the leaves, the static eval decides where it is far from the window: reverse futility
+// pruning cuts off a node whose eval is above beta by a margin per ply left, razoring drops
+// into quiescence below alpha by its margin per ply, and futility pruning skips the quiet
+// moves of a node whose eval is below alpha by its margin per ply
+const REVERSE_FUTILITY_MAX_DEPTH: u8 = 3;
+const REVERSE_FUTILITY_MARGIN: i16 = 120;
+const RAZORING_MAX_DEPTH: u8 = 2;
+const RAZORING_MARGIN: i16 = 300;
+const FUTILITY_MAX_DEPTH: u8 = 2;
+const FUTILITY_MARGIN: i16 = 150;
+// Iterations from this depth on start with a window this wide on either side of the score of
+// the previous one, which doubles on every fail, and is dropped once it gets this wide
+const ASPIRATION_MIN_DEPTH: u8 = 4;
+const ASPIRATION_WINDOW: i16 = 25;
+const ASPIRATION_MAX_WINDOW: i16 = 800;
+pub const DEFAULT_LMR_BASE: u16 = 75;
+pub const MAX_LMR_BASE: u16 = 400;
+pub const DEFAULT_LMR_DIVISOR: u16 = 225;
+pub const MIN_LMR_DIVISOR: u16 = 100;
+pub const MAX_LMR_DIVISOR: u16 = 1000;
+// At null window nodes this deep, ProbCut searches the good captures this many plies
+// shallower against beta raised by a margin, and a capture still at least that good predicts
+// a cutoff at full depth. Multi-cut searches the first moves this many plies shallower, and
+// predicts a cutoff when enough of them fail high. Every so many predictions of either are
+// searched at full depth instead, to count how often they are right. In the self-play of
+// `locochess bench probcut` at 150 ms a move, one checked ProbCut prediction in about 200
+// was wrong without a margin, one in about 340 with the default margin, and wider margins
+// predicted fewer cutoffs without being right much more often.
+const PROBCUT_MIN_DEPTH: u8 = 5;
+const PROBCUT_REDUCTION: u8 = 4;
+pub const DEFAULT_PROBCUT_MARGIN: u16 = 100;
+pub const MAX_PROBCUT_MARGIN: u16 = 1000;
+const MULTI_CUT_MIN_DEPTH: u8 = 6;
+const MULTI_CUT_REDUCTION: u8 = 3;
+const MULTI_CUT_MOVES: usize = 6;
+const MULTI_CUT_CUTOFFS: usize = 3;
+const PREDICTION_CHECK_INTERVAL: u64 = 8;
+
+/// How a cutoff was predicted by shallower searches.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Prediction {
+    ProbCut,
+    MultiCut,
+}
+
+/// What the search keeps about a node while the minimax library searches its moves.
+#[derive(Default)]
+pub struct SearchedNode {
+    hash: u64,
+    // The best move stored for the node in the table, which is searched first
+    hash_move: Option<ChessMove>,
+    static_eval: Option<i16>,
+    // Whether the node verifies a null move cutoff, and must not pass again
+    verifying_null_move: bool,
+    // A predicted cutoff that is checked, and so searched as if there was none
+    checked_prediction: Option<Prediction>,
+}
 
 pub struct Position {
     chess_move: Option<ChessMove>,
@@ -25,333 +118,1589 @@ pub struct Position {
 impl Position {
     pub fn new(chess_move: Option<ChessMove>, alpha: i16, beta: i16, depth: u8) -> Position {
         Position {
-            chess_move: chess_move,
+            chess_move,
             potential_next_moves: None,
             next_best: None,
-            alpha: alpha,
-            beta: beta,
-            depth: depth,
+            alpha,
+            beta,
+            depth,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct SearchOptions {
+    pub move_ordering: bool,
+    // Memory budget of the search tree, over which its least valuable branches are evicted
+    pub tree_memory_mb: usize,
+    // How much worse than equal a draw is for the side the search is for
+    pub contempt: i16,
+    // Whether a side whose position is good enough even after passing is cut off early
+    pub null_move: bool,
+    // Whether quiet moves late in the move order are searched shallower, by
+    // lmr_base + ln(depth left) * ln(move number) / lmr_divisor plies, both in hundredths
+    pub late_move_reductions: bool,
+    pub lmr_base: u16,
+    pub lmr_divisor: u16,
+    // Whether checks, only moves and pawn pushes to the seventh rank are searched a ply deeper
+    pub extensions: bool,
+    // Whether quiescence searches the quiet moves that give check at its first ply too
+    pub quiet_checks: bool,
+    pub futility_pruning: bool,
+    pub reverse_futility_pruning: bool,
+    pub razoring: bool,
+    // Whether shallower searches may predict the cutoffs of deeper ones, ProbCut with beta
+    // raised by probcut_margin
+    pub probcut: bool,
+    pub probcut_margin: u16,
+    pub multi_cut: bool,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        SearchOptions {
+            move_ordering: true,
+            tree_memory_mb: DEFAULT_TREE_MEMORY_MB,
+            contempt: 0,
+            null_move: true,
+            late_move_reductions: true,
+            lmr_base: DEFAULT_LMR_BASE,
+            lmr_divisor: DEFAULT_LMR_DIVISOR,
+            extensions: true,
+            quiet_checks: true,
+            futility_pruning: true,
+            reverse_futility_pruning: true,
+            razoring: true,
+            probcut: true,
+            probcut_margin: DEFAULT_PROBCUT_MARGIN,
+            multi_cut: true,
+        }
+    }
+}
+
+impl SearchOptions {
+    /// The options for one of `threads` searches, which share the tree memory budget.
+    pub fn per_thread(self, threads: usize) -> SearchOptions {
+        SearchOptions {
+            tree_memory_mb: (self.tree_memory_mb / threads.max(1)).max(MIN_TREE_MEMORY_MB),
+            ..self
         }
     }
 }
 
+/// Limits from the go command, besides the maximum depth and the move time.
+#[derive(Clone, Debug, Default)]
+pub struct SearchLimits {
+    pub nodes: Option<u64>,
+    // Stops as soon as a mate is found
+    pub mate: bool,
+    // Ignores the move time, searching until stopped or another limit is reached
+    pub infinite: bool,
+    // Only these moves are searched at the root, if any
+    pub search_moves: Vec<ChessMove>,
+}
+
+/// The game before the searched position, for repetitions and the fifty-move rule.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GameHistory {
+    // Hashes of the positions since the last capture or pawn move, oldest first
+    pub hashes: Vec<u64>,
+    // Plies since the last capture or pawn move, up to the searched position
+    pub halfmove_clock: u16,
+}
+
+impl GameHistory {
+    /// The history of the position after `mv` is played in `board`, where `board` is the
+    /// position this history leads up to.
+    pub fn after(&self, board: &Board, mv: ChessMove) -> GameHistory {
+        if is_irreversible(board, mv) {
+            return GameHistory::default();
+        }
+        let mut hashes = self.hashes.clone();
+        hashes.push(board.get_hash());
+        GameHistory {
+            hashes,
+            halfmove_clock: self.halfmove_clock + 1,
+        }
+    }
+
+    /// Whether `board`, reached at the end of this history, is drawn by the fifty-move rule
+    /// or by repeating a position for the third time.
+    pub fn is_draw(&self, board: &Board) -> bool {
+        let mut hashes = self.hashes.clone();
+        hashes.push(board.get_hash());
+        is_fifty_move_draw(board, self.halfmove_clock) || repeats(&hashes, self.halfmove_clock, self.hashes.len())
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Statistics {
+    pub nodes: u64,
+    pub pruned: u64,
+    pub evaluated: u64,
+    pub table_cutoffs: u64,
+    pub null_cutoffs: u64,
+    pub reduced: u64,
+    pub re_searched: u64,
+    pub failed_high: u64,
+    pub failed_low: u64,
+    pub extended: u64,
+    pub futility_pruned: u64,
+    pub reverse_futility_cutoffs: u64,
+    pub razored: u64,
+    pub probcut_cutoffs: u64,
+    pub probcut_checked: u64,
+    pub probcut_right: u64,
+    pub multi_cut_cutoffs: u64,
+    pub multi_cut_checked: u64,
+    pub multi_cut_right: u64,
+    pub evicted: u64,
+}
+
+/// State shared by all threads searching the same position.
+#[derive(Default)]
+pub struct SharedState {
+    // Set from outside, by the stop command
+    pub stop: AtomicBool,
+    // Set by a parallel search itself, when the threads it started are no longer needed
+    pub abort: AtomicBool,
+    pub nodes: AtomicU64,
+    // Set while searching on the opponent's time, the clock only starts on ponderhit
+    pub pondering: AtomicBool,
+}
+
+impl SharedState {
+    pub fn is_stopped(&self) -> bool {
+        self.stop.load(Ordering::Relaxed) || self.abort.load(Ordering::Relaxed)
+    }
+}
+
 pub struct Search {
     tree: Tree<Position>,
-    color: Color,
     board: Board,
+    // The side the search plays for, which the contempt is for wherever it is to move
+    color: Color,
+    table: Arc<TranspositionTable>,
+    shared: Arc<SharedState>,
+    depth_offset: u8,
+    ordering: MoveOrdering,
+    options: SearchOptions,
+    limits: SearchLimits,
+    // Hashes of the game history, the root and the line being searched
+    position_hashes: Vec<u64>,
+    // Where the root is in the position hashes
+    game_plies: usize,
+    // Halfmove clocks of the root and the line being searched
+    halfmove_clocks: Vec<u16>,
+    // Moves from the root to the node being searched, with a stand-in for a pass
+    line: Vec<ChessMove>,
+    info_output: bool,
+    start_time: Instant,
+    last_info: Instant,
+    last_time_check: Instant,
+    deadline: Option<Instant>,
+    // Set until the first iteration is finished, so that there is always a move to return
+    must_complete: bool,
+    aborted: bool,
+    // Set for the search verifying a null move cutoff, which must not pass again
+    verifying_null_move: bool,
+    // Plies the line being searched is extended by, at most the depth of the iteration, so
+    // that no line gets more than twice as deep as the others
+    line_extensions: u8,
+    best_move: Option<ChessMove>,
+    score: i16,
+    // Whether the score of the last iteration is exact, or fell outside of its window
+    score_bound: Bound,
+    current_depth: u8,
+    // The deepest iteration of iterative deepening that was finished
+    completed_depth: u8,
+    seldepth: u8,
+    number_of_nodes: u64,
+    number_of_pruned: u64,
+    number_of_evaluated: u64,
+    number_of_table_cutoffs: u64,
+    number_of_null_cutoffs: u64,
+    number_of_reduced: u64,
+    number_of_re_searched: u64,
+    number_of_failed_high: u64,
+    number_of_failed_low: u64,
+    number_of_extended: u64,
+    number_of_futility_pruned: u64,
+    number_of_reverse_futility_cutoffs: u64,
+    number_of_razored: u64,
+    number_of_probcut_cutoffs: u64,
+    number_of_probcut_checked: u64,
+    number_of_probcut_right: u64,
+    number_of_multi_cut_cutoffs: u64,
+    number_of_multi_cut_checked: u64,
+    number_of_multi_cut_right: u64,
+    number_of_evicted: u64,
+}
+
+/// Counts the nodes of a search done by the minimax library into the statistics of the
+/// search that started it `ply` plies from its root, and abandons it when the time is up.
+struct Observed<'a> {
+    search: &'a mut Search,
+    ply: usize,
+}
+
+impl Observer for Observed<'_> {
+    fn enter(&mut self, ply: usize) -> bool {
+        let search = &mut *self.search;
+        search.number_of_nodes += 1;
+        search.seldepth = search.seldepth.max((self.ply + ply) as u8);
+        if search.time_is_up() {
+            search.aborted = true;
+            return false;
+        }
+        true
+    }
+
+    fn evaluated(&mut self) {
+        self.search.number_of_evaluated += 1;
+    }
+
+    fn cutoff(&mut self) {
+        self.search.number_of_pruned += 1;
+    }
 }
 
 impl Search {
+    #[cfg(test)]
     pub fn new(board: &Board, color: Color) -> Search {
+        Search::with_table(board, color, Arc::new(TranspositionTable::default()))
+    }
+
+    /// Creates a search that reuses the results stored in `table` by earlier
+    /// or concurrently running searches.
+    pub fn with_table(board: &Board, color: Color, table: Arc<TranspositionTable>) -> Search {
         info!("Creating Search with color {:?}", color);
         show_board(*board);
         Search {
-            tree: Tree::new(Position::new(None, i16::MIN, i16::MAX, 0)),
-            color: color,
-            board: board.clone(),
+            tree: Tree::new(Position::new(None, -INFINITY, INFINITY, 0)),
+            board: *board,
+            color,
+            table,
+            shared: Arc::new(SharedState::default()),
+            depth_offset: 0,
+            ordering: MoveOrdering::default(),
+            options: SearchOptions::default(),
+            limits: SearchLimits::default(),
+            position_hashes: vec![board.get_hash()],
+            game_plies: 0,
+            halfmove_clocks: vec![0],
+            line: vec![],
+            info_output: false,
+            start_time: Instant::now(),
+            last_info: Instant::now(),
+            last_time_check: Instant::now(),
+            deadline: None,
+            must_complete: false,
+            aborted: false,
+            verifying_null_move: false,
+            line_extensions: 0,
+            best_move: None,
+            score: 0,
+            score_bound: Bound::Exact,
+            current_depth: 0,
+            completed_depth: 0,
+            seldepth: 0,
+            number_of_nodes: 0,
+            number_of_pruned: 0,
+            number_of_evaluated: 0,
+            number_of_table_cutoffs: 0,
+            number_of_null_cutoffs: 0,
+            number_of_reduced: 0,
+            number_of_re_searched: 0,
+            number_of_failed_high: 0,
+            number_of_failed_low: 0,
+            number_of_extended: 0,
+            number_of_futility_pruned: 0,
+            number_of_reverse_futility_cutoffs: 0,
+            number_of_razored: 0,
+            number_of_probcut_cutoffs: 0,
+            number_of_probcut_checked: 0,
+            number_of_probcut_right: 0,
+            number_of_multi_cut_cutoffs: 0,
+            number_of_multi_cut_checked: 0,
+            number_of_multi_cut_right: 0,
+            number_of_evicted: 0,
+        }
+    }
+
+    /// Makes the search count its nodes into, and stop on, the given shared state.
+    pub fn set_shared(&mut self, shared: Arc<SharedState>) {
+        self.shared = shared;
+    }
+
+    /// Makes iterative deepening skip the first `depth_offset` depths, so that helper
+    /// threads do not all search the same depth at the same time.
+    pub fn set_depth_offset(&mut self, depth_offset: u8) {
+        self.depth_offset = depth_offset;
+    }
+
+    pub fn set_options(&mut self, options: SearchOptions) {
+        self.options = options;
+        self.tree.set_memory_budget(options.tree_memory_mb * 1024 * 1024);
+    }
+
+    /// Makes the search print UCI `info` lines after every iteration and every second.
+    pub fn set_info_output(&mut self, info_output: bool) {
+        self.info_output = info_output;
+    }
+
+    pub fn statistics(&self) -> Statistics {
+        Statistics {
+            nodes: self.number_of_nodes,
+            pruned: self.number_of_pruned,
+            evaluated: self.number_of_evaluated,
+            table_cutoffs: self.number_of_table_cutoffs,
+            null_cutoffs: self.number_of_null_cutoffs,
+            reduced: self.number_of_reduced,
+            re_searched: self.number_of_re_searched,
+            failed_high: self.number_of_failed_high,
+            failed_low: self.number_of_failed_low,
+            extended: self.number_of_extended,
+            futility_pruned: self.number_of_futility_pruned,
+            reverse_futility_cutoffs: self.number_of_reverse_futility_cutoffs,
+            razored: self.number_of_razored,
+            probcut_cutoffs: self.number_of_probcut_cutoffs,
+            probcut_checked: self.number_of_probcut_checked,
+            probcut_right: self.number_of_probcut_right,
+            multi_cut_cutoffs: self.number_of_multi_cut_cutoffs,
+            multi_cut_checked: self.number_of_multi_cut_checked,
+            multi_cut_right: self.number_of_multi_cut_right,
+            evicted: self.number_of_evicted,
+        }
+    }
+
+    /// Searches to exactly `max_depth` plies, optionally with a narrowed root window. When
+    /// the score falls outside of the window, it is only a bound, and after failing low the
+    /// best move stays the one found before.
+    pub fn run(&mut self, max_depth: u8, alpha: Option<i16>, beta: Option<i16>) -> ChessMove {
+        self.reset_root_window();
+        if let Some(val) = alpha {
+            self.tree.root_mut().data.alpha = val;
+        }
+        if let Some(val) = beta {
+            self.tree.root_mut().data.beta = val;
         }
+        self.aborted = false;
+        self.current_depth = max_depth;
+        self.seldepth = 0;
+        self.search_root(max_depth);
+        if !self.aborted && self.info_output {
+            let info = self.iteration_info();
+            reply(UciMessage::Info(info));
+        }
+        self.log_statistics();
+        self.best_move.unwrap()
     }
 
-    pub fn run(&mut self, max_depth: u8, alpha: Option<i16>, beta: Option<i16>) -> ChessMove{
-        let mut number_of_pruned = 0;
-        let mut number_of_evaluated = 0;
-        match alpha {
-            Some(val) => {
-                self.tree.root.borrow_mut().data.alpha = val;
+    /// Iterative deepening: searches depth 1, 2, 3, ... until either `max_depth` is reached
+    /// or `move_time` is spent, and returns the best move of the last completed iteration.
+    pub fn run_timed(&mut self, max_depth: u8, move_time: Duration) -> ChessMove {
+        let start = Instant::now();
+        self.start_time = start;
+        self.last_time_check = start;
+        self.deadline = if self.limits.infinite {
+            None
+        } else {
+            Some(start + move_time)
+        };
+        self.best_move = None;
+        self.must_complete = true;
+        self.completed_depth = 0;
+        for depth in (1 + self.depth_offset).min(max_depth)..=max_depth {
+            let best_move = self.run_with_aspiration(depth);
+            if self.aborted {
+                info!("Search aborted at depth {}, after {:?}", depth, start.elapsed());
+                break;
+            }
+            self.must_complete = false;
+            self.completed_depth = depth;
+            info!(
+                "Finished depth {} in {:?}, best move: {}",
+                depth,
+                start.elapsed(),
+                chess_move_to_string(&best_move)
+            );
+            if self.limits.mate && self.score >= MATE_THRESHOLD {
+                break;
+            }
+            // The next iteration would take several times longer than this one,
+            // so there is no point in starting it when half of the time is gone
+            if !self.follow_ponder_clock() && self.time_left() <= move_time / 2 {
+                break;
             }
-            None => {}
         }
-        match beta {
-            Some(val) => {
-                self.tree.root.borrow_mut().data.alpha = val;
+        self.deadline = None;
+        self.best_move.unwrap()
+    }
+
+    /// Searches to `depth` plies with a narrow window around the score of the previous
+    /// iteration, which cuts off more than the full window. Whenever the score falls outside
+    /// of the window, the side it fell out of is widened and the depth searched again.
+    fn run_with_aspiration(&mut self, depth: u8) -> ChessMove {
+        if depth < ASPIRATION_MIN_DEPTH || self.completed_depth == 0 || is_mate_score(self.score) {
+            return self.run(depth, None, None);
+        }
+        let mut delta = ASPIRATION_WINDOW;
+        let mut alpha = self.score - delta;
+        let mut beta = self.score + delta;
+        loop {
+            let best_move = self.run(depth, Some(alpha), Some(beta));
+            if self.aborted {
+                return best_move;
             }
-            None => {}
+            delta *= 2;
+            match self.score_bound {
+                Bound::Exact => return best_move,
+                Bound::Upper => {
+                    self.number_of_failed_low += 1;
+                    alpha = if delta >= ASPIRATION_MAX_WINDOW {
+                        -INFINITY
+                    } else {
+                        self.score - delta
+                    };
+                }
+                Bound::Lower => {
+                    self.number_of_failed_high += 1;
+                    beta = if delta >= ASPIRATION_MAX_WINDOW {
+                        INFINITY
+                    } else {
+                        self.score + delta
+                    };
+                }
+            }
+            info!("Score {} outside of the window at depth {}, searching with {}..{}", self.score, depth, alpha, beta);
+        }
+    }
+
+    /// Searches to `depth` plies with the given window and returns the score of the root
+    /// from the point of view of its side to move, or `None` if the search was stopped.
+    pub fn score(&mut self, depth: u8, alpha: i16, beta: i16) -> Option<i16> {
+        self.aborted = false;
+        self.current_depth = depth;
+        self.seldepth = 0;
+        let board = self.board;
+        // Unlike the root of a game, the root of a part of a search can be a draw
+        if self.is_draw(&board) {
+            return Some(self.draw_score(&board));
         }
-        let mut depth_correction = 0;
-        if self.board.side_to_move() != self.color {
-            //info!("using depth correction");
-            depth_correction = 1;
+        let score = self.alpha_beta(&board, depth, alpha, beta);
+        self.count_remaining_nodes();
+        if self.aborted {
+            None
         } else {
-            //info!("not using depth correction");
+            Some(score)
+        }
+    }
+
+    pub fn set_limits(&mut self, limits: SearchLimits) {
+        self.limits = limits;
+    }
+
+    /// The deepest ply the last search reached, with extensions and quiescence.
+    pub fn seldepth(&self) -> u8 {
+        self.seldepth
+    }
+
+    /// The depth of the last iteration `run_timed` finished.
+    pub fn completed_depth(&self) -> u8 {
+        self.completed_depth
+    }
+
+    pub fn set_deadline(&mut self, deadline: Option<Instant>) {
+        self.deadline = deadline;
+    }
+
+    /// Sets the game that led to the position, so that repeating its positions and the
+    /// fifty-move rule are scored as draws.
+    pub fn set_history(&mut self, history: &GameHistory) {
+        self.position_hashes = history.hashes.clone();
+        self.position_hashes.push(self.board.get_hash());
+        self.game_plies = history.hashes.len();
+        self.halfmove_clocks = vec![history.halfmove_clock];
+    }
+
+    fn history(&self) -> GameHistory {
+        GameHistory {
+            hashes: self.position_hashes[..self.game_plies].to_vec(),
+            halfmove_clock: self.halfmove_clocks[0],
         }
-        let mut i = 0;
+    }
+
+    /// Continues from the position after the played moves with the subtree already searched
+    /// for it, keeping the bounds, best children and move ordering found so far. Returns false
+    /// and leaves the search as it was if the tree does not reach that position.
+    pub fn reroot(&mut self, played: &[ChessMove]) -> bool {
+        let mut board = self.board;
+        let mut history = self.history();
+        let mut depth = 0;
+        for mv in played {
+            match self.tree.find_child(|position| position.chess_move == Some(*mv)) {
+                Some(child) => {
+                    self.tree.goto_child(child);
+                    history = history.after(&board, *mv);
+                    board = board.make_move_new(*mv);
+                    depth += 1;
+                }
+                None => {
+                    for _ in 0..depth {
+                        self.tree.goto_parent();
+                    }
+                    return false;
+                }
+            }
+        }
+        self.tree.make_current_root();
+        self.tree.root_mut().data.chess_move = None;
+        // Mate scores count the plies from the root, which is now closer to them
+        self.tree.for_each_in_subtree(|position| {
+            position.depth -= depth;
+            position.alpha = mate_score_from_root(position.alpha, played.len());
+            position.beta = mate_score_from_root(position.beta, played.len());
+        });
+        self.board = board;
+        self.set_history(&history);
+        self.ordering.advance(played.len());
+        info!("Rerooted search after {}", moves_to_string(played));
+        true
+    }
+
+    fn reset_root_window(&mut self) {
+        let root = self.tree.root_mut();
+        root.data.alpha = -INFINITY;
+        root.data.beta = INFINITY;
+    }
+
+    fn search_root(&mut self, depth: u8) {
+        let alpha = self.tree.root().data.alpha;
+        let beta = self.tree.root().data.beta;
+        let board = self.board;
+        let score = self.alpha_beta(&board, depth, alpha, beta);
+        if self.aborted {
+            return;
+        }
+        self.score = score;
+        self.score_bound = bound_of(score, alpha, beta);
+        // No move reaches the window, so the best of them is no better than the one before
+        if self.score_bound == Bound::Upper && self.best_move.is_some() {
+            return;
+        }
+        let next_best = self.tree.root().data.next_best;
+        if let Some(best_idx) = next_best {
+            self.tree.goto_child(best_idx);
+            self.best_move = self.tree.current().data.chess_move;
+            self.tree.goto_parent();
+        }
+        self.show_board_from_moves(&[]);
+    }
+
+    /// Negamax alpha-beta of the position after the line being searched, run by the minimax
+    /// library with this search as its hooks. The tree keeps every visited node between
+    /// iterations, so the best child of the previous iteration is always searched first.
+    fn alpha_beta(&mut self, board: &Board, depth_left: u8, alpha: i16, beta: i16) -> i16 {
+        let game = ChessGame::at_ply(*board, self.line.len());
+        // Only a stopped search is abandoned, which sets `aborted`
+        minimax::hooks::search(&game, depth_left as u32, alpha as i32, beta as i32, self).map_or(0, |score| score as i16)
+    }
+
+    /// Records the score of the current node in the tree, and returns it for the library.
+    fn settle(&mut self, score: i16) -> i32 {
+        self.tree.current_mut().data.alpha = score;
+        score as i32
+    }
+
+    /// Orders the moves of the current node into the tree, unless they already are. At the
+    /// root, only the moves to search are kept, if any of them is legal.
+    fn prepare_moves(&mut self, board: &Board, hash_move: Option<ChessMove>) {
+        if self.tree.current().data.potential_next_moves.is_some() {
+            return;
+        }
+        let ply = self.line.len();
+        let mut legal_moves: Vec<ChessMove> = if self.options.move_ordering {
+            self.ordering.ordered_moves(board, hash_move, ply)
+        } else {
+            MoveGen::new_legal(board).collect()
+        };
+        let search_moves = &self.limits.search_moves;
+        if ply == 0 && legal_moves.iter().any(|mv| search_moves.contains(mv)) {
+            legal_moves.retain(|mv| search_moves.contains(mv));
+        }
+        // The moves to search are the only data of a node on the heap
+        self.tree.set_heap_size(legal_moves.capacity() * size_of::<ChessMove>());
+        self.tree.current_mut().data.potential_next_moves = Some(legal_moves);
+    }
+
+    /// The moves of the current node in the order they are searched: the children expanded
+    /// in earlier iterations, starting with the previous best, then the moves not searched
+    /// yet, which are popped from the end. The null move child is no real move.
+    fn moves_in_search_order(&mut self) -> Vec<ChessMove> {
+        let previous_best = self.tree.current().data.next_best;
+        let null_child = self.tree.find_child(|position| position.chess_move.is_none());
+        let children: Vec<usize> = previous_best
+            .into_iter()
+            .chain((0..self.tree.number_of_children()).filter(|i| Some(*i) != previous_best && Some(*i) != null_child))
+            .collect();
         let mut moves = vec![];
-        loop {
-            i += 1;
-            if i > 2i32.pow(20) {
-                //println!("reached limit");
+        for idx in children {
+            self.tree.goto_child(idx);
+            moves.extend(self.tree.current().data.chess_move);
+            self.tree.goto_parent();
+        }
+        let next_moves = self.tree.current().data.potential_next_moves.as_deref().unwrap_or_default();
+        moves.extend(next_moves.iter().rev());
+        moves
+    }
+
+    /// Number of legal moves of the current node, searched or not.
+    fn number_of_moves(&self) -> usize {
+        let null_child = self.tree.find_child(|position| position.chess_move.is_none());
+        self.tree.number_of_children() - null_child.is_some() as usize
+            + self.tree.current().data.potential_next_moves.as_ref().map_or(0, |moves| moves.len())
+    }
+
+    /// Goes to the child of `mv`, adding it with the window of the node if it is not searched
+    /// yet. Its move is then the last of the moves still to search.
+    fn goto_child_for(&mut self, mv: ChessMove, alpha: i16, beta: i16) {
+        match self.tree.find_child(|position| position.chess_move == Some(mv)) {
+            Some(idx) => self.tree.goto_child(idx),
+            None => {
+                let next_move = self.tree.current_mut().data.potential_next_moves.as_mut().unwrap().pop();
+                debug_assert_eq!(next_move, Some(mv));
+                let depth = self.tree.current().data.depth + 1;
+                self.add_child(Position::new(Some(mv), -beta, -alpha, depth));
+                self.tree.goto_last_child();
+            }
+        }
+    }
+
+    /// The static eval of a node close enough to the leaves for it to decide, or `None`
+    /// where it must not: at the root, in check, where only a search shows the way out, and
+    /// when the window is about mates, which no margin covers.
+    fn static_eval_near_leaves(&self, board: &Board, ply: usize, depth_left: u8, alpha: i16, beta: i16) -> Option<i16> {
+        let options = &self.options;
+        if !(options.futility_pruning || options.reverse_futility_pruning || options.razoring)
+            || ply == 0
+            || depth_left > REVERSE_FUTILITY_MAX_DEPTH.max(RAZORING_MAX_DEPTH).max(FUTILITY_MAX_DEPTH)
+            || *board.checkers() != EMPTY
+            || is_mate_score(alpha)
+            || is_mate_score(beta)
+        {
+            return None;
+        }
+        Some(eval(board, &[], board.side_to_move()))
+    }
+
+    /// The score of a node decided by its static eval alone, before any move is searched:
+    /// the eval itself when it is far enough above beta, or the score of quiescence when the
+    /// eval is far enough below alpha that quiescence confirms it. Without the quiet checks
+    /// option, quiescence misses a mate by a quiet check, so a node with one is not razored.
+    /// With it, quiescence searches them at its first ply, which is the razored node.
+    fn prune_by_static_eval(
+        &mut self,
+        board: &Board,
+        ply: usize,
+        depth_left: u8,
+        alpha: i16,
+        beta: i16,
+        static_eval: i16,
+    ) -> Option<i16> {
+        let depth = depth_left as i16;
+        if self.options.reverse_futility_pruning
+            && depth_left <= REVERSE_FUTILITY_MAX_DEPTH
+            && static_eval - REVERSE_FUTILITY_MARGIN * depth >= beta
+        {
+            self.number_of_reverse_futility_cutoffs += 1;
+            return Some(static_eval - REVERSE_FUTILITY_MARGIN * depth);
+        }
+        if self.options.razoring
+            && depth_left <= RAZORING_MAX_DEPTH
+            && static_eval + RAZORING_MARGIN * depth < alpha
+            && (self.options.quiet_checks || !has_checking_move(board))
+        {
+            let score = self.quiescence(board, alpha, beta, ply);
+            if self.aborted {
+                return Some(0);
+            }
+            if score < alpha {
+                self.number_of_razored += 1;
+                return Some(score);
+            }
+        }
+        None
+    }
+
+    /// Whether a quiet move is not worth searching, because the static eval of the node is
+    /// so far below alpha that no quiet move brings it up there this close to the leaves.
+    /// Captures, promotions and checks are always searched.
+    fn is_futile(
+        &self,
+        board: &Board,
+        mv: ChessMove,
+        child: &Board,
+        depth_left: u8,
+        alpha: i16,
+        static_eval: Option<i16>,
+    ) -> bool {
+        let Some(static_eval) = static_eval else {
+            return false;
+        };
+        self.options.futility_pruning
+            && depth_left <= FUTILITY_MAX_DEPTH
+            && static_eval + FUTILITY_MARGIN * depth_left as i16 <= alpha
+            && !is_mate_score(alpha)
+            && !is_capture(board, &mv)
+            && mv.get_promotion().is_none()
+            && *child.checkers() == EMPTY
+    }
+
+    /// One ply for a move that gives check, that is the only legal move, or that pushes a
+    /// pawn to the seventh rank, as long as the line is not extended as far as it may be.
+    fn extension(&mut self, board: &Board, mv: ChessMove, child: &Board, only_move: bool) -> u8 {
+        if !self.options.extensions || self.line_extensions >= self.current_depth {
+            return 0;
+        }
+        let color = board.side_to_move();
+        let to_seventh_rank = board.piece_on(mv.get_source()) == Some(Piece::Pawn)
+            && mv.get_dest().get_rank() == color.to_seventh_rank();
+        if only_move || *child.checkers() != EMPTY || to_seventh_rank {
+            self.number_of_extended += 1;
+            1
+        } else {
+            0
+        }
+    }
+
+    /// How many plies shallower than the others the `searched`-th quiet move is searched,
+    /// growing with the depth left and the number of moves searched before it. Captures,
+    /// promotions and checks are searched to the full depth, and so is every move that
+    /// would otherwise go straight to quiescence.
+    fn late_move_reduction(&self, board: &Board, mv: ChessMove, child: &Board, depth_left: u8, searched: usize) -> u8 {
+        if !self.options.late_move_reductions
+            || depth_left < LMR_MIN_DEPTH
+            || searched < LMR_FULL_DEPTH_MOVES
+            || is_capture(board, &mv)
+            || mv.get_promotion().is_some()
+            || *child.checkers() != EMPTY
+        {
+            return 0;
+        }
+        // In hundredths of a ply, like the options
+        let scaled = (depth_left as f64).ln() * ((searched + 1) as f64).ln() * 10000.0 / self.options.lmr_divisor as f64;
+        let reduction = (self.options.lmr_base as f64 + scaled) as u16 / 100;
+        reduction.min(depth_left as u16 - 2) as u8
+    }
+
+    /// Whether the side to move may pass to show that its position is good enough even
+    /// without a move. Not when in check, where passing is illegal, not with only pawns,
+    /// where having to move is often what loses, not right after the other side passed and
+    /// not when the window is about mates, which a pass cannot prove.
+    fn null_move_allowed(&self, board: &Board, ply: usize, depth_left: u8, beta: i16) -> bool {
+        let color = board.side_to_move();
+        self.options.null_move
+            && ply > 0
+            && depth_left >= NULL_MOVE_MIN_DEPTH
+            && self.tree.current().data.chess_move.is_some()
+            && *board.checkers() == EMPTY
+            && (-MATE_THRESHOLD..MATE_THRESHOLD).contains(&beta)
+            && non_pawn_material(board, color) > 0
+            && eval(board, &[], color) >= beta
+    }
+
+    /// Searches the position after passing with a reduced depth and a null window at `beta`,
+    /// and returns the score to cut off with if passing is still at least `beta`. The null
+    /// move has its own child without a move in the tree.
+    fn null_move_search(&mut self, board: &Board, depth_left: u8, alpha: i16, beta: i16) -> Option<i16> {
+        let passed = board.null_move()?;
+        let reduction = NULL_MOVE_REDUCTION + depth_left / NULL_MOVE_DEPTH_STEP;
+        let depth = (depth_left - 1).saturating_sub(reduction);
+        match self.tree.find_child(|position| position.chess_move.is_none()) {
+            Some(idx) => self.tree.goto_child(idx),
+            None => {
+                let tree_depth = self.tree.current().data.depth + 1;
+                self.add_child(Position::new(None, -beta, -beta + 1, tree_depth));
+                self.tree.goto_last_child();
+            }
+        }
+        // No repetition reaches back over a pass
+        self.enter_position(&passed, true);
+        // Below, only the number of moves played matters, so a stand-in keeps the ply right
+        self.line.push(ChessMove::default());
+        let score = -self.alpha_beta(&passed, depth, -beta, -beta + 1);
+        self.line.pop();
+        self.leave_position();
+        self.tree.goto_parent();
+        if self.aborted || score < beta {
+            return None;
+        }
+
+        if non_pawn_material(board, board.side_to_move()) <= NULL_MOVE_VERIFICATION_MATERIAL {
+            self.verifying_null_move = true;
+            let verified = self.alpha_beta(board, depth_left - 1, beta - 1, beta);
+            let current = self.tree.current_mut();
+            current.data.alpha = alpha;
+            current.data.beta = beta;
+            if self.aborted || verified < beta {
+                return None;
+            }
+        }
+        self.number_of_null_cutoffs += 1;
+        // A mate found after passing is not one the side to move can force
+        Some(if score >= MATE_THRESHOLD { beta } else { score })
+    }
+
+    /// Whether shallower searches predict that the node fails high, by ProbCut or else by
+    /// multi-cut. Only null window nodes are predicted, where a cutoff is expected, and
+    /// never in check or with a mate score as beta.
+    fn predict_cutoff(&mut self, board: &Board, depth_left: u8, alpha: i16, beta: i16) -> Option<Prediction> {
+        if self.line.is_empty() || alpha.saturating_add(1) != beta || *board.checkers() != EMPTY || is_mate_score(beta) {
+            return None;
+        }
+        if self.options.probcut && depth_left >= PROBCUT_MIN_DEPTH && self.probcut(board, depth_left, beta) {
+            return Some(Prediction::ProbCut);
+        }
+        if self.options.multi_cut
+            && depth_left >= MULTI_CUT_MIN_DEPTH
+            && !self.aborted
+            && self.multi_cut(board, depth_left, beta)
+        {
+            return Some(Prediction::MultiCut);
+        }
+        None
+    }
+
+    /// Whether a capture that wins enough material to get beta raised by the margin, by its
+    /// static exchange evaluation, still gets there in a search `PROBCUT_REDUCTION` plies
+    /// shallower.
+    fn probcut(&mut self, board: &Board, depth_left: u8, beta: i16) -> bool {
+        let raised_beta = beta.saturating_add(self.options.probcut_margin as i16);
+        if is_mate_score(raised_beta) {
+            return false;
+        }
+        let static_eval = eval(board, &[], board.side_to_move());
+        let mut captures: Vec<ChessMove> = MoveGen::new_legal(board)
+            .filter(|mv| {
+                (is_capture(board, mv) || mv.get_promotion().is_some()) && static_eval + see(board, *mv) >= raised_beta
+            })
+            .collect();
+        captures.sort_by_key(|mv| -mvv_lva(board, mv));
+        let depth = depth_left - 1 - PROBCUT_REDUCTION;
+        captures.into_iter().any(|mv| {
+            let score = self.search_child_for(board, mv, depth, raised_beta);
+            !self.aborted && score >= raised_beta
+        })
+    }
+
+    /// Whether `MULTI_CUT_CUTOFFS` of the first `MULTI_CUT_MOVES` moves in the search order
+    /// fail high in a search `MULTI_CUT_REDUCTION` plies shallower.
+    fn multi_cut(&mut self, board: &Board, depth_left: u8, beta: i16) -> bool {
+        let mut first_moves = self.moves_in_search_order();
+        first_moves.truncate(MULTI_CUT_MOVES);
+
+        let depth = depth_left - 1 - MULTI_CUT_REDUCTION;
+        let mut cutoffs = 0;
+        for (i, mv) in first_moves.iter().enumerate() {
+            let score = self.search_child_for(board, *mv, depth, beta);
+            if self.aborted {
+                return false;
+            }
+            if score >= beta {
+                cutoffs += 1;
+            }
+            if cutoffs == MULTI_CUT_CUTOFFS || cutoffs + first_moves.len() - i - 1 < MULTI_CUT_CUTOFFS {
                 break;
             }
-            //info!("start loop");
-            self.show_board_from_moves(&moves);
-            if self.tree.current.borrow().data.depth < max_depth {
-                //info!("depth not max");
-                if self
-                    .tree
-                    .current
-                    .borrow()
-                    .data
-                    .potential_next_moves
-                    .is_none()
-                {
-                    let board = board_from_moves(self.board.clone(), &moves);
-                    let legal_moves = get_possible_moves(&board);
-                    self.tree.current.borrow_mut().data.potential_next_moves = Some(legal_moves);
-                }
-                let alpha = self.tree.current.borrow().data.alpha;
-                let beta = self.tree.current.borrow().data.beta;
-                if alpha >= beta{
-                    //info!("pruning");
-                    number_of_pruned += 1;
-                    let child_idx = self.tree.current.borrow().index;
-                    if self.move_up(&mut moves) {
-                        self.show_board_from_moves(&moves);
-                        if self.corrected_depth(depth_correction) % 2 == 0 {
-                            if alpha > self.tree.current.borrow().data.alpha {
-                                self.tree.current.borrow_mut().data.alpha = alpha;
-                                self.tree.current.borrow_mut().data.next_best = child_idx;
-                                //self.show_board_from_moves(&moves);
-                            }
-                        } else {
-                            if beta > self.tree.current.borrow().data.beta {
-                                self.tree.current.borrow_mut().data.beta = beta;
-                                self.tree.current.borrow_mut().data.next_best = child_idx;
-                                //self.show_board_from_moves(&moves);
-                            }
-                        }
-                    }
-                    // here I think the parent should be updated with beta
+        }
+        cutoffs == MULTI_CUT_CUTOFFS
+    }
+
+    /// Searches the child of `mv` `depth` plies deep, with a null window just below `beta`,
+    /// and returns its score for the side to move. A move not searched before gets its
+    /// child now, so that the moves still to search do not have it twice.
+    fn search_child_for(&mut self, board: &Board, mv: ChessMove, depth: u8, beta: i16) -> i16 {
+        match self.tree.find_child(|position| position.chess_move == Some(mv)) {
+            Some(idx) => self.tree.goto_child(idx),
+            None => {
+                if let Some(next_moves) = self.tree.current_mut().data.potential_next_moves.as_mut() {
+                    next_moves.retain(|next_move| *next_move != mv);
                 }
+                let tree_depth = self.tree.current().data.depth + 1;
+                self.add_child(Position::new(Some(mv), -beta, -beta + 1, tree_depth));
+                self.tree.goto_last_child();
+            }
+        }
+        let child = board.make_move_new(mv);
+        self.enter_position(&child, is_irreversible(board, mv));
+        self.line.push(mv);
+        let score = -self.alpha_beta(&child, depth, -beta, -beta + 1);
+        self.line.pop();
+        self.leave_position();
+        self.tree.goto_parent();
+        score
+    }
 
-                let next_move = self
-                    .tree
-                    .current
-                    .borrow_mut()
-                    .data
-                    .potential_next_moves
-                    .as_mut()
-                    .unwrap()
-                    .pop();
-
-                match next_move {
-                    Some(mv) => {
-                        //info!("there exists next move");
-                        let alpha = self.tree.current.borrow().data.alpha;
-                        let beta = self.tree.current.borrow().data.beta;
-                        let depth = self.tree.current.borrow().data.depth + 1;
-
-                        self.tree
-                            .add_child(Position::new(next_move, alpha, beta, depth));
-                        self.tree.goto_last_child();
-
-                        moves.push(mv);
-                    }
-                    None => {
-                        number_of_evaluated += 1;
-
-                        let eval = eval(&self.board, &moves, self.color);
-                        let child_idx = self.tree.current.borrow().index;
-
-                        if self.corrected_depth(depth_correction) % 2 == 0 {
-                            //info!("even modulo");
-                            if self.tree.has_no_child() {
-                                //info!("has no child!");
-                                // here should only be checkmate or stalemate
-                                let alpha = max(eval, self.tree.current.borrow().data.alpha);
-                                self.tree.current.borrow_mut().data.alpha = alpha;
-                                    
-                            }
-                            let alpha = self.tree.current.borrow().data.alpha;
-                            if self.move_up(&mut moves) {
-                                self.show_board_from_moves(&moves);
-                                if alpha < self.tree.current.borrow().data.beta {
-                                    //info!("updating beta");
-                                    self.tree.current.borrow_mut().data.beta = alpha;
-                                    self.tree.current.borrow_mut().data.next_best = child_idx;
-                                    //self.show_board_from_moves(&moves);
-                                }
-                            } else {
-                                break;
-                            }
-                        } else {
-                            //info!("uneven modulo");
-                            if self.tree.has_no_child() {
-                                //info!("has no child!");
-                                // here should only be checkmate or stalemate
-                                let beta = min(eval, self.tree.current.borrow().data.beta);
-                                self.tree.current.borrow_mut().data.beta = beta;
-                            }
-                            let beta = self.tree.current.borrow().data.beta;
-                            if self.move_up(&mut moves) {
-                                self.show_board_from_moves(&moves);
-
-                                if beta > self.tree.current.borrow().data.alpha {
-                                    //info!("updating alpha");
-                                    self.tree.current.borrow_mut().data.alpha = beta;
-                                    self.tree.current.borrow_mut().data.next_best = child_idx;
-                                    //self.show_board_from_moves(&moves);
-                                }
-                            } else {
-                                break;
-                            }
-                        }
-                    }
+    /// Whether the prediction is to be checked by a search at full depth, which is the
+    /// case for every `PREDICTION_CHECK_INTERVAL`-th one of its kind.
+    fn should_check(&self, prediction: Prediction) -> bool {
+        let predictions = match prediction {
+            Prediction::ProbCut => self.number_of_probcut_cutoffs + self.number_of_probcut_checked,
+            Prediction::MultiCut => self.number_of_multi_cut_cutoffs + self.number_of_multi_cut_checked,
+        };
+        predictions.is_multiple_of(PREDICTION_CHECK_INTERVAL)
+    }
+
+    fn enter_position(&mut self, child: &Board, irreversible: bool) {
+        let clock = if irreversible {
+            0
+        } else {
+            self.halfmove_clocks.last().unwrap() + 1
+        };
+        self.position_hashes.push(child.get_hash());
+        self.halfmove_clocks.push(clock);
+    }
+
+    fn leave_position(&mut self) {
+        self.position_hashes.pop();
+        self.halfmove_clocks.pop();
+    }
+
+    /// Whether the last position of the searched line is drawn by the fifty-move rule, or by
+    /// repetition: of a position of the game for the third time, or of one since the root.
+    fn is_draw(&self, board: &Board) -> bool {
+        let clock = *self.halfmove_clocks.last().unwrap();
+        is_fifty_move_draw(board, clock) || repeats(&self.position_hashes, clock, self.game_plies)
+    }
+
+    /// Score of a draw in `board`, for the side to move there.
+    fn draw_score(&self, board: &Board) -> i16 {
+        draw_score(board, self.color, self.options.contempt)
+    }
+
+    /// Adds a child to the current node, evicting the least valuable branches first if the
+    /// tree is out of memory. Only when the nodes that cannot be evicted fill the budget is
+    /// it exceeded.
+    fn add_child(&mut self, position: Position) {
+        if self.tree.is_full() {
+            self.evict();
+        }
+        self.tree.add_child_over_budget(position);
+    }
+
+    /// Collapses fully searched subtrees next to the path being searched into summary nodes,
+    /// which keep their bounds but drop their children, until a quarter of the tree budget is
+    /// free again. Branches off the best line go first, and deep ones before shallow ones, as
+    /// they are the least likely to be searched again and hold the least work each. The table
+    /// still has the best moves of the collapsed nodes, so expanding them again is cheap.
+    fn evict(&mut self) {
+        if !self.tree.has_budget() {
+            return;
+        }
+        let before = self.tree.len();
+        // Children taken from the root down to the current node, which are all being searched
+        let mut path = vec![];
+        while self.tree.has_parent() {
+            path.push(self.tree.current().index.unwrap());
+            self.tree.goto_parent();
+        }
+        path.reverse();
+
+        'eviction: for keep_best in [true, false] {
+            for level in (0..=path.len()).rev() {
+                self.tree.goto_root();
+                for &i in &path[..level] {
+                    self.tree.goto_child(i);
                 }
-            } else {
-                number_of_evaluated += 1;
-                let (min_eval, max_eval) = eval_with_children(&self.board, &moves, self.color);
-                let child_idx = self.tree.current.borrow().index;
-
-                if self.corrected_depth(depth_correction) % 2 == 0 {
-                    let alpha = max(self.tree.current.borrow().data.alpha, max_eval);
-                    self.tree.current.borrow_mut().data.alpha = alpha;
-                    if self.move_up(&mut moves) {
-                        if alpha < self.tree.current.borrow().data.beta {
-                            self.tree.current.borrow_mut().data.beta = alpha;
-                            self.tree.current.borrow_mut().data.next_best = child_idx;
-                            self.show_board_from_moves(&moves);
-                        }
-                    } else {
-                        break;
+                let on_path = path.get(level).copied();
+                let best = self.tree.current().data.next_best;
+                for i in 0..self.tree.number_of_children() {
+                    if Some(i) == on_path || (keep_best && Some(i) == best) {
+                        continue;
                     }
-                } else {
-                    let beta = min(self.tree.current.borrow().data.beta, min_eval);
-                    self.tree.current.borrow_mut().data.beta = beta;
-                    if self.move_up(&mut moves) {
-                        if beta > self.tree.current.borrow().data.alpha {
-                            self.tree.current.borrow_mut().data.alpha = beta;
-                            self.tree.current.borrow_mut().data.next_best = child_idx;
-                            self.show_board_from_moves(&moves);
-                        }
-                    } else {
-                        break;
+                    self.tree.goto_child(i);
+                    if self.tree.has_children() {
+                        self.tree.remove_children();
+                        // The moves of the removed children have to be generated again
+                        let summary = &mut self.tree.current_mut().data;
+                        summary.potential_next_moves = None;
+                        summary.next_best = None;
+                        self.tree.set_heap_size(0);
+                    }
+                    self.tree.goto_parent();
+                    if !self.tree.is_filled_to(3, 4) {
+                        break 'eviction;
                     }
                 }
             }
         }
 
+        self.tree.goto_root();
+        for &i in &path {
+            self.tree.goto_child(i);
+        }
+        self.number_of_evicted += (before - self.tree.len()) as u64;
+    }
+
+    /// Searches captures and promotions until the position is quiet, so that the static
+    /// evaluation is never taken in the middle of an exchange. Positions in check are searched
+    /// with all evasions, as standing pat is not an option there. With the quiet checks
+    /// option, quiet checks are searched at the first ply too.
+    fn quiescence(&mut self, board: &Board, alpha: i16, beta: i16, ply: usize) -> i16 {
+        let mut game = ChessGame::at_ply(*board, ply).with_contempt(self.color, self.options.contempt);
+        if self.options.quiet_checks {
+            game = game.with_quiet_checks();
+        }
+        let mut observer = Observed { search: self, ply };
+        match minimax::quiescence(&game, alpha as i32, beta as i32, &mut observer) {
+            Some(score) => score as i16,
+            None => 0,
+        }
+    }
+
+    fn time_is_up(&mut self) -> bool {
+        if !self.number_of_nodes.is_multiple_of(NODES_BETWEEN_TIME_CHECKS) {
+            return false;
+        }
+        self.shared.nodes.fetch_add(NODES_BETWEEN_TIME_CHECKS, Ordering::Relaxed);
+        if self.info_output && self.last_info.elapsed() >= INFO_INTERVAL {
+            let info = self.progress_info();
+            reply(UciMessage::Info(info));
+        }
+        if self.must_complete {
+            return false;
+        }
+        if self.shared.is_stopped() {
+            return true;
+        }
+        if let Some(nodes) = self.limits.nodes {
+            if self.shared.nodes.load(Ordering::Relaxed) >= nodes {
+                return true;
+            }
+        }
+        if self.follow_ponder_clock() {
+            return false;
+        }
+        match self.deadline {
+            Some(deadline) => Instant::now() >= deadline,
+            None => false,
+        }
+    }
+
+    /// Moves the deadline forward by the time spent pondering since the last check, and
+    /// returns whether the search is still pondering.
+    fn follow_ponder_clock(&mut self) -> bool {
+        let now = Instant::now();
+        let pondering = self.shared.pondering.load(Ordering::Relaxed);
+        if pondering {
+            self.deadline = self.deadline.map(|deadline| deadline + (now - self.last_time_check));
+        }
+        self.last_time_check = now;
+        pondering
+    }
+
+    fn time_left(&self) -> Duration {
+        self.deadline
+            .map_or(Duration::MAX, |deadline| deadline.saturating_duration_since(Instant::now()))
+    }
+
+    /// Best line of the last completed iteration: the `next_best` chain of the tree,
+    /// continued with the best moves stored in the transposition table.
+    pub fn principal_variation(&mut self) -> Vec<ChessMove> {
+        let mut pv = vec![];
+        let mut board = self.board;
         loop {
-            info!("Checking if has parent");
-            if self.tree.has_parent(){
-                info!("Going to parent");
-                self.tree.goto_parent();
-            } else {
+            let next_best = self.tree.current().data.next_best;
+            let Some(best_idx) = next_best else {
                 break;
-            } 
+            };
+            self.tree.goto_child(best_idx);
+            let mv = self.tree.current().data.chess_move.unwrap();
+            board = board.make_move_new(mv);
+            pv.push(mv);
+        }
+        for _ in 0..pv.len() {
+            self.tree.goto_parent();
         }
+        extend_from_table(&self.table, board, &mut pv);
+        pv
+    }
+
+    fn iteration_info(&mut self) -> Vec<UciInfoAttribute> {
+        let pv = self.principal_variation();
+        let mut score = uci_score(self.score);
+        if let UciInfoAttribute::Score {
+            lower_bound,
+            upper_bound,
+            ..
+        } = &mut score
+        {
+            *lower_bound = (self.score_bound == Bound::Lower).then_some(true);
+            *upper_bound = (self.score_bound == Bound::Upper).then_some(true);
+        }
+        let mut info = vec![
+            UciInfoAttribute::Depth(self.current_depth),
+            UciInfoAttribute::SelDepth(self.seldepth),
+            score,
+        ];
+        info.extend(self.progress_info().into_iter().skip(2));
+        info.push(UciInfoAttribute::Pv(pv));
+        info
+    }
+
+    fn progress_info(&mut self) -> Vec<UciInfoAttribute> {
+        self.last_info = Instant::now();
+        let elapsed = self.start_time.elapsed();
+        let nodes = self.total_nodes();
+        let nps = nodes * 1000 / (elapsed.as_millis() as u64).max(1);
+        vec![
+            UciInfoAttribute::Depth(self.current_depth),
+            UciInfoAttribute::SelDepth(self.seldepth),
+            UciInfoAttribute::Nodes(nodes),
+            UciInfoAttribute::Nps(nps),
+            UciInfoAttribute::Time(VampDuration::milliseconds(elapsed.as_millis() as i64)),
+            UciInfoAttribute::HashFull(self.table.hashfull()),
+        ]
+    }
 
-        let next_move_idx = self.tree.current.borrow().data.next_best;
-        self.show_board_from_moves(&moves);
-        info!("number of pruned: {}", number_of_pruned);
-        info!("number of evaluated: {}", number_of_evaluated);
-        info!("alpha: {}", self.tree.root.borrow().data.alpha);
-        info!("beta: {}", self.tree.root.borrow().data.beta);
-        info!("next_best: {:?}", self.tree.root.borrow().data.next_best);
+    /// Nodes searched by all threads sharing the state of this search.
+    pub fn total_nodes(&self) -> u64 {
+        self.shared.nodes.load(Ordering::Relaxed) + self.number_of_nodes % NODES_BETWEEN_TIME_CHECKS
+    }
 
-        self.tree.goto_child(next_move_idx.unwrap());
-        let next_move = self.tree.current.borrow().data.chess_move.unwrap();
-        next_move
+    /// Nodes are only counted into the shared counter in batches, this adds what is left. A
+    /// search that hands its nodes to others calls it once, when it is done.
+    pub fn count_remaining_nodes(&self) {
+        self.shared
+            .nodes
+            .fetch_add(self.number_of_nodes % NODES_BETWEEN_TIME_CHECKS, Ordering::Relaxed);
     }
 
-    fn corrected_depth(&self, depth_correction: u8) -> u8 {
-        self.tree.current.borrow().data.depth + depth_correction
+    fn log_statistics(&self) {
+        let statistics = self.statistics();
+        info!("number of nodes: {}", statistics.nodes);
+        info!("number of pruned: {}", statistics.pruned);
+        info!("number of evaluated: {}", statistics.evaluated);
+        info!("number of table cutoffs: {}", statistics.table_cutoffs);
+        info!("number of null move cutoffs: {}", statistics.null_cutoffs);
+        info!("number of reduced: {}, re-searched: {}", statistics.reduced, statistics.re_searched);
+        info!("aspiration windows failed high: {}, low: {}", statistics.failed_high, statistics.failed_low);
+        info!("number of extended: {}", statistics.extended);
+        info!(
+            "number of futility pruned: {}, reverse futility cutoffs: {}, razored: {}",
+            statistics.futility_pruned, statistics.reverse_futility_cutoffs, statistics.razored
+        );
+        info!(
+            "number of ProbCut cutoffs: {}, predictions right: {} of {} checked",
+            statistics.probcut_cutoffs, statistics.probcut_right, statistics.probcut_checked
+        );
+        info!(
+            "number of multi-cut cutoffs: {}, predictions right: {} of {} checked",
+            statistics.multi_cut_cutoffs, statistics.multi_cut_right, statistics.multi_cut_checked
+        );
+        info!("number of evicted: {}", statistics.evicted);
+        info!("tree size: {} nodes, {} bytes", self.tree.len(), self.tree.memory_usage());
+        info!("alpha: {}", self.tree.root().data.alpha);
+        info!("beta: {}", self.tree.root().data.beta);
+        info!("next_best: {:?}", self.tree.root().data.next_best);
     }
 
-    fn move_up(&mut self, moves: &mut Vec<ChessMove>) -> bool {
-        if self.tree.has_parent() {
+    fn show_board_from_moves(&mut self, moves: &[ChessMove]) {
+        if moves.len() < 2 {
+            info!("---------------------------------");
+            info!("moves: {}", moves_to_string(moves));
+            info!("next best moves:");
+            let mut i = 0;
+            loop {
+                let next_best = self.tree.current().data.next_best;
+                let Some(best_idx) = next_best else {
+                    break;
+                };
+                i += 1;
+                self.tree.goto_child(best_idx);
+                let current = &self.tree.current().data;
+                info!(
+                    "    move: {}, alpha: {}, beta: {}",
+                    chess_move_to_string(&current.chess_move.unwrap()),
+                    current.alpha,
+                    current.beta
+                );
+            }
+            for _ in 0..i {
+                self.tree.goto_parent();
+            }
+            info!("depth: {}", moves.len());
+            info!("index: {:?}", self.tree.current().index);
+            info!("alpha: {}", self.tree.current().data.alpha);
+            info!("beta: {}", self.tree.current().data.beta);
+            show_board(board_from_moves(self.board, moves));
+            info!("---------------------------------");
+        }
+    }
+}
+
+/// Counts the nodes of the main search, and abandons it when the time is up.
+impl Observer for Search {
+    fn enter(&mut self, _ply: usize) -> bool {
+        self.number_of_nodes += 1;
+        self.seldepth = self.seldepth.max(self.line.len() as u8);
+        if self.time_is_up() {
+            self.aborted = true;
+            return false;
+        }
+        true
+    }
+
+    fn evaluated(&mut self) {
+        self.number_of_evaluated += 1;
+    }
+
+    fn cutoff(&mut self) {
+        self.number_of_pruned += 1;
+    }
+}
+
+/// Everything the search adds to the alpha-beta of the minimax library: the tree, the table,
+/// draws, the move ordering, the pruning, extensions and reductions. The ply of a node is the
+/// length of the line, as the library counts its plies from wherever a search starts, and
+/// null move and cutoff prediction start searches of their own.
+impl Hooks<ChessGame> for Search {
+    type Node = SearchedNode;
+
+    fn probe(&mut self, game: &ChessGame, window: Window) -> Probe<SearchedNode> {
+        let verifying_null_move = std::mem::take(&mut self.verifying_null_move);
+        let (alpha, beta) = (window.alpha as i16, window.beta as i16);
+        {
+            let current = self.tree.current_mut();
+            current.data.alpha = alpha;
+            current.data.beta = beta;
+        }
+        let board = game.board();
+        let ply = self.line.len();
+        // The root always has to produce a move
+        if ply > 0 && self.is_draw(board) {
+            let score = self.draw_score(board);
+            return Probe::Value(self.settle(score));
+        }
+
+        let hash = board.get_hash();
+        let hash_move = match self.table.probe(hash) {
+            Some(mut entry) => {
+                entry.score = mate_score_to_root(entry.score, ply);
+                // The root is always searched, as it has to produce a move
+                if ply > 0 && entry.depth >= window.depth as u8 && entry.cuts_off(alpha, beta) {
+                    self.number_of_table_cutoffs += 1;
+                    return Probe::Value(self.settle(entry.score));
+                }
+                entry.best_move
+            }
+            None => None,
+        };
+        Probe::Search(SearchedNode {
+            hash,
+            hash_move,
+            verifying_null_move,
+            ..SearchedNode::default()
+        })
+    }
+
+    fn leaf(&mut self, game: &ChessGame, node: &mut SearchedNode, window: Window, _ply: usize) -> Option<i32> {
+        let (alpha, beta) = (window.alpha as i16, window.beta as i16);
+        let ply = self.line.len();
+        let score = self.quiescence(game.board(), alpha, beta, ply);
+        if self.aborted {
+            return None;
+        }
+        self.table.store(Entry {
+            hash: node.hash,
+            best_move: None,
+            score: mate_score_from_root(score, ply),
+            depth: 0,
+            bound: bound_of(score, alpha, beta),
+        });
+        Some(self.settle(score))
+    }
+
+    fn prune(&mut self, game: &ChessGame, node: &mut SearchedNode, window: Window) -> Prune {
+        let board = game.board();
+        let ply = self.line.len();
+        let depth_left = window.depth as u8;
+        let (alpha, beta) = (window.alpha as i16, window.beta as i16);
+        node.static_eval = self.static_eval_near_leaves(board, ply, depth_left, alpha, beta);
+        if let Some(static_eval) = node.static_eval {
+            if let Some(score) = self.prune_by_static_eval(board, ply, depth_left, alpha, beta, static_eval) {
+                if self.aborted {
+                    return Prune::Abandon;
+                }
+                return Prune::Value(self.settle(score));
+            }
+        }
+
+        self.prepare_moves(board, node.hash_move);
+        // Checkmate and stalemate are scored, not pruned
+        if self.number_of_moves() == 0 {
+            return Prune::Search;
+        }
+
+        if !node.verifying_null_move && self.null_move_allowed(board, ply, depth_left, beta) {
+            if let Some(score) = self.null_move_search(board, depth_left, alpha, beta) {
+                return Prune::Value(self.settle(score));
+            }
+        }
+
+        if !node.verifying_null_move && !self.aborted {
+            if let Some(prediction) = self.predict_cutoff(board, depth_left, alpha, beta) {
+                if self.aborted {
+                    return Prune::Abandon;
+                }
+                if self.should_check(prediction) {
+                    node.checked_prediction = Some(prediction);
+                } else {
+                    match prediction {
+                        Prediction::ProbCut => self.number_of_probcut_cutoffs += 1,
+                        Prediction::MultiCut => self.number_of_multi_cut_cutoffs += 1,
+                    }
+                    return Prune::Value(self.settle(beta));
+                }
+            }
+        }
+        if self.aborted {
+            Prune::Abandon
+        } else {
+            Prune::Search
+        }
+    }
+
+    fn moves(&mut self, _game: &ChessGame, _node: &mut SearchedNode) -> Vec<ChessMove> {
+        self.moves_in_search_order()
+    }
+
+    fn terminal(&mut self, game: &ChessGame, node: &mut SearchedNode) -> i32 {
+        // Only checkmate or stalemate, draws by rule are found by the probe
+        let board = game.board();
+        let score = if *board.checkers() == EMPTY {
+            self.draw_score(board)
+        } else {
+            eval(board, &[], board.side_to_move())
+        };
+        self.table.store(Entry {
+            hash: node.hash,
+            best_move: None,
+            score,
+            depth: MAX_DEPTH,
+            bound: Bound::Exact,
+        });
+        let score = mate_score_to_root(score, self.line.len());
+        self.settle(score)
+    }
+
+    fn visit(
+        &mut self,
+        game: &ChessGame,
+        node: &mut SearchedNode,
+        mv: ChessMove,
+        child: &ChessGame,
+        window: Window,
+        searched: usize,
+    ) -> Visit {
+        let (board, child) = (game.board(), child.board());
+        let depth_left = window.depth as u8;
+        let (alpha, beta) = (window.alpha as i16, window.beta as i16);
+        let only_move = self.number_of_moves() == 1;
+        self.goto_child_for(mv, alpha, beta);
+        if searched > 0 && self.is_futile(board, mv, child, depth_left, alpha, node.static_eval) {
+            self.number_of_futility_pruned += 1;
             self.tree.goto_parent();
-            moves.pop();
-            true
+            // The skipped move is only known to score no more than the futility bound
+            let bound = node.static_eval.map_or(-minimax::INFINITY, |static_eval| {
+                (static_eval + FUTILITY_MARGIN * depth_left as i16) as i32
+            });
+            return Visit::Skip { bound };
+        }
+
+        let ply = self.line.len();
+        self.enter_position(child, is_irreversible(board, mv));
+        self.line.push(mv);
+        let extension = self.extension(board, mv, child, only_move);
+        self.line_extensions += extension;
+        // Only the moves after the first one are searched with a null window, and reduced
+        let reduction = if searched > 0 && ply > 0 && *board.checkers() == EMPTY && extension == 0 {
+            self.late_move_reduction(board, mv, child, depth_left, searched)
         } else {
-            false
+            0
+        };
+        if reduction > 0 {
+            self.number_of_reduced += 1;
+        }
+        Visit::Search {
+            extension: extension as u32,
+            reduction: reduction as u32,
         }
     }
 
-    fn show_board_from_moves(&mut self, moves: &Vec<ChessMove>) {
-        if moves.len() < 2{
-            info!("---------------------------------");
-            info!("moves: {}", moves_to_string(&moves));
-            info!("next best moves:");
-            let mut i = 0;
-            loop {
-                let next_best = self.tree.current.borrow().data.next_best;
-                match next_best {
-                    Some(best_idx) => {
-                        i += 1;
-                        self.tree.goto_child(best_idx);
-                        let current = &self.tree.current.borrow().data;
-                        info!(
-                            "    move: {}, alpha: {}, beta: {}", 
-                            chess_move_to_string(&current.chess_move.unwrap()), 
-                            current.alpha, 
-                            current.beta
-                        );
-                    },
-                    None => {break;}
-                }
+    fn leave(&mut self, _game: &ChessGame, _node: &mut SearchedNode, _mv: ChessMove, extension: u32) {
+        self.line_extensions -= extension as u8;
+        self.line.pop();
+        self.leave_position();
+        self.tree.goto_parent();
+    }
+
+    fn re_searched(&mut self) {
+        self.number_of_re_searched += 1;
+    }
+
+    fn refuted(&mut self, game: &ChessGame, _node: &mut SearchedNode, mv: ChessMove, depth: u32) {
+        self.ordering.add_cutoff(game.board(), mv, self.line.len(), depth as u8);
+    }
+
+    fn store(
+        &mut self,
+        _game: &ChessGame,
+        node: &mut SearchedNode,
+        window: Window,
+        value: i32,
+        best_move: Option<ChessMove>,
+    ) {
+        let best = value as i16;
+        let beta = window.beta as i16;
+        match node.checked_prediction {
+            Some(Prediction::ProbCut) => {
+                self.number_of_probcut_checked += 1;
+                self.number_of_probcut_right += (best >= beta) as u64;
             }
-            for _ in 0..i {
-                self.tree.goto_parent();
+            Some(Prediction::MultiCut) => {
+                self.number_of_multi_cut_checked += 1;
+                self.number_of_multi_cut_right += (best >= beta) as u64;
             }
-            info!("depth: {}", moves.len());
-            info!("index: {:?}", self.tree.current.borrow().index);
-            info!("alpha: {}", self.tree.current.borrow().data.alpha);
-            info!("beta: {}", self.tree.current.borrow().data.beta);
-            show_board(board_from_moves(self.board, moves));
-            info!("---------------------------------");
-            info!("");
+            None => {}
         }
+
+        self.table.store(Entry {
+            hash: node.hash,
+            best_move,
+            score: mate_score_from_root(best, self.line.len()),
+            depth: window.depth as u8,
+            bound: bound_of(best, window.alpha as i16, beta),
+        });
+        let best_idx = best_move.and_then(|mv| self.tree.find_child(|position| position.chess_move == Some(mv)));
+        self.tree.current_mut().data.next_best = best_idx;
+        self.settle(best);
     }
 }
 
-fn board_from_moves(initial_board: Board, moves: &Vec<ChessMove>) -> Board {
-    let mut board = initial_board.clone();
+fn board_from_moves(initial_board: Board, moves: &[ChessMove]) -> Board {
+    let mut board = initial_board;
     for mv in moves.iter() {
         board = board.make_move_new(*mv);
     }
     board
 }
 
-fn get_possible_moves(board: &Board) -> Vec<ChessMove>{
-    let mut seed = board.get_hash();
-    if seed > 10000 {
-        seed -= 10000;
+/// Mate scores are reported as the number of moves to the mate.
+pub fn uci_score(score: i16) -> UciInfoAttribute {
+    if is_mate_score(score) {
+        UciInfoAttribute::from_mate(mate_in_moves(score) as i8)
+    } else {
+        UciInfoAttribute::from_centipawns(score as i32)
+    }
+}
+
+/// Extends a line ending in `board` with the best moves stored in the table.
+pub fn extend_from_table(table: &TranspositionTable, mut board: Board, line: &mut Vec<ChessMove>) {
+    while line.len() < MAX_DEPTH as usize {
+        match table.probe(board.get_hash()).and_then(|entry| entry.best_move) {
+            Some(mv) if board.legal(mv) => {
+                board = board.make_move_new(mv);
+                line.push(mv);
+            }
+            _ => break,
+        }
     }
-    let mut legal_moves: Vec<ChessMove> = MoveGen::new_legal(&board).collect();
-    for i in 0..legal_moves.len() {
-        seed += legal_moves[i].get_source().to_int() as u64 * legal_moves[i].get_dest().to_int() as u64;
-        let move_to = (seed % legal_moves.len() as u64) as usize;
-        legal_moves.swap(i, move_to);
+}
+
+fn is_fifty_move_draw(board: &Board, halfmove_clock: u16) -> bool {
+    // Unless the last move mated
+    halfmove_clock >= FIFTY_MOVES && (*board.checkers() == EMPTY || MoveGen::new_legal(board).len() > 0)
+}
+
+/// Whether the last of `hashes` repeats a position after `first_searched`, or twice repeats
+/// one before it. Only the last `halfmove_clock` positions, since the last capture or pawn
+/// move, can repeat.
+fn repeats(hashes: &[u64], halfmove_clock: u16, first_searched: usize) -> bool {
+    let current = hashes.len() - 1;
+    let mut earlier_in_game = 0;
+    // Positions with the same side to move, of which the first possible is four plies back
+    for back in (4..=halfmove_clock as usize).step_by(2) {
+        let Some(earlier) = current.checked_sub(back) else {
+            break;
+        };
+        if hashes[earlier] == hashes[current] {
+            if earlier >= first_searched {
+                return true;
+            }
+            earlier_in_game += 1;
+            if earlier_in_game == 2 {
+                return true;
+            }
+        }
     }
-    legal_moves
+    false
 }
 
+/// Whether the move resets the fifty-move rule, after which no earlier position can repeat.
+fn is_irreversible(board: &Board, mv: ChessMove) -> bool {
+    board.piece_on(mv.get_source()) == Some(Piece::Pawn) || is_capture(board, &mv)
+}
+
+fn has_checking_move(board: &Board) -> bool {
+    MoveGen::new_legal(board).any(|mv| *board.make_move_new(mv).checkers() != EMPTY)
+}
+
+fn bound_of(score: i16, alpha: i16, beta: i16) -> Bound {
+    if score <= alpha {
+        Bound::Upper
+    } else if score >= beta {
+        Bound::Lower
+    } else {
+        Bound::Exact
+    }
+}
 
-pub fn moves_to_string( moves: &Vec<ChessMove>) -> String{
-    moves.iter().map(|mv| chess_move_to_string(mv))
-    .fold(String::new(), |acc: String, e: String| acc + &e + ", ")
+pub fn moves_to_string(moves: &[ChessMove]) -> String {
+    moves
+        .iter()
+        .map(chess_move_to_string)
+        .fold(String::new(), |acc: String, e: String| acc + &e + ", ")
 }
 
 pub fn chess_move_to_string(mv: &ChessMove) -> String {
-    format!("{}:{}", mv.get_source().to_string(), mv.get_dest().to_string())
+    format!("{}:{}", mv.get_source(), mv.get_dest())
 }
 
-pub fn assert_mv_eq(mv: &ChessMove, expcted: &str){
+#[cfg(test)]
+pub fn assert_mv_eq(mv: &ChessMove, expcted: &str) {
     assert_eq!(chess_move_to_string(mv), expcted);
 }
 
-pub fn assert_mv_ne(mv: &ChessMove, expcted: &str){
+#[cfg(test)]
+pub fn assert_mv_ne(mv: &ChessMove, expcted: &str) {
     assert_ne!(chess_move_to_string(mv), expcted);
 }
 
-pub fn assert_mv_src_eq(mv: &ChessMove, expcted: &str){
+#[cfg(test)]
+pub fn assert_mv_src_eq(mv: &ChessMove, expcted: &str) {
     assert_eq!(chess_move_to_string(mv)[..2], expcted[..2]);
 }
 
 #[cfg(test)]
 mod tests {
-    use chess::{CastleRights, BoardBuilder};
+    use chess::{BoardBuilder, BoardStatus, CastleRights, Game, Piece, Square, ALL_SQUARES};
+    use proptest::prelude::*;
+    use std::str::FromStr;
 
+    use crate::engine::eval::CHECKMATE_EVAL;
+    use crate::engine::game::ChessGame;
     use crate::engine::utils::board_from_textboard;
 
     use super::*;
@@ -388,6 +1737,818 @@ mod tests {
         search.run(2, None, None);
     }
 
+    #[test]
+    fn test_iterative_deepening_stops_when_move_time_is_spent() {
+        let board = Game::new().current_position();
+        let mut search = Search::new(&board, Color::White);
+        let start = Instant::now();
+        let best = search.run_timed(MAX_DEPTH, Duration::from_millis(200));
+        assert!(start.elapsed() < Duration::from_millis(1000));
+        assert!(board.legal(best));
+    }
+
+    #[test]
+    fn test_iterative_deepening_finds_checkmate_in_one() {
+        let board = Board::from_str("7k/7P/6PK/8/8/8/8/8 w - - 0 1").unwrap();
+        let mut search = Search::new(&board, Color::White);
+        let best = search.run_timed(3, Duration::from_secs(10));
+        assert_eq!(board.make_move_new(best).status(), BoardStatus::Checkmate);
+    }
+
+    #[test]
+    fn test_rerooting_at_grandchild_keeps_its_subtree() {
+        let board = Board::from_str("r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4").unwrap();
+        let mut search = Search::new(&board, Color::White);
+        search.run(4, None, None);
+        let pv = search.principal_variation();
+        let played = [pv[0], pv[1]];
+
+        assert!(search.reroot(&played));
+        assert!(search.tree.has_children());
+        assert!(!search.tree.has_parent());
+        assert_eq!(search.board, board.make_move_new(played[0]).make_move_new(played[1]));
+        search.tree.goto_child(0);
+        assert_eq!(search.tree.current().data.depth, 1);
+        search.tree.goto_parent();
+
+        let best = search.run(4, None, None);
+        assert!(search.board.legal(best));
+    }
+
+    #[test]
+    fn test_rerooting_fails_for_unsearched_moves() {
+        let board = Board::default();
+        let mut search = Search::new(&board, Color::White);
+        search.run(1, None, None);
+        let unsearched = [ChessMove::new(Square::E2, Square::E4, None), ChessMove::new(Square::E7, Square::E5, None)];
+        assert!(!search.reroot(&unsearched));
+        assert_eq!(search.board, board);
+        assert!(!search.tree.has_parent());
+    }
+
+    #[test]
+    fn test_node_limit_stops_search() {
+        let board = Board::default();
+        let mut search = Search::new(&board, Color::White);
+        search.set_limits(SearchLimits {
+            nodes: Some(5000),
+            infinite: true,
+            ..SearchLimits::default()
+        });
+        search.run_timed(MAX_DEPTH, Duration::ZERO);
+        assert!(search.shared.nodes.load(Ordering::Relaxed) < 5000 + NODES_BETWEEN_TIME_CHECKS);
+    }
+
+    #[test]
+    fn test_search_moves_restrict_root_moves() {
+        let board = Board::default();
+        let only = ChessMove::new(Square::A2, Square::A3, None);
+        let mut search = Search::new(&board, Color::White);
+        search.set_limits(SearchLimits {
+            search_moves: vec![only],
+            ..SearchLimits::default()
+        });
+        assert_eq!(search.run_timed(3, Duration::from_secs(10)), only);
+    }
+
+    #[test]
+    fn test_mate_limit_stops_at_first_mate() {
+        let board = Board::from_str("7k/7P/6PK/8/8/8/8/8 w - - 0 1").unwrap();
+        let mut search = Search::new(&board, Color::White);
+        search.set_limits(SearchLimits {
+            mate: true,
+            infinite: true,
+            ..SearchLimits::default()
+        });
+        let best = search.run_timed(MAX_DEPTH, Duration::ZERO);
+        assert_eq!(board.make_move_new(best).status(), BoardStatus::Checkmate);
+        assert!(search.current_depth < 3);
+    }
+
+    #[test]
+    fn test_eviction_keeps_tree_within_budget_and_result_unchanged() {
+        let board = Board::from_str("r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4").unwrap();
+        let mut unbounded = Search::new(&board, Color::White);
+        let best = unbounded.run(4, None, None);
+        let mut bounded = Search::new(&board, Color::White);
+        bounded.tree.set_node_budget(Some(500));
+
+        assert_eq!(bounded.run(4, None, None), best);
+        assert_eq!(bounded.score, unbounded.score);
+        assert!(unbounded.tree.len() > 500);
+        assert!(bounded.tree.len() <= 500);
+        assert!(bounded.statistics().evicted > 0);
+    }
+
+    #[test]
+    fn test_tree_memory_counts_the_moves_to_search() {
+        let board = Board::from_str("r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4").unwrap();
+        let mut search = Search::new(&board, Color::White);
+        search.tree.set_memory_budget(64 * 1024);
+        search.run(4, None, None);
+        assert!(search.tree.memory_used() <= 64 * 1024);
+        assert!(search.tree.memory_used() > search.tree.len() * Tree::<Position>::NODE_SIZE);
+        assert!(search.statistics().evicted > 0);
+    }
+
+    #[test]
+    fn test_iterative_deepening_within_tree_budget() {
+        let board = Board::default();
+        let mut search = Search::new(&board, Color::White);
+        search.tree.set_node_budget(Some(300));
+        let best = search.run_timed(5, Duration::from_secs(60));
+        assert!(board.legal(best));
+        assert!(search.tree.len() <= 300);
+        assert_eq!(search.principal_variation()[0], best);
+    }
+
+    #[test]
+    fn test_move_ordering_prunes_more_and_evaluates_less() {
+        let board = Board::from_str("r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4").unwrap();
+        let mut unordered = Search::new(&board, Color::White);
+        unordered.set_options(SearchOptions {
+            move_ordering: false,
+            ..SearchOptions::default()
+        });
+        unordered.run(4, None, None);
+        let mut ordered = Search::new(&board, Color::White);
+        ordered.run(4, None, None);
+
+        let unordered = unordered.statistics();
+        let ordered = ordered.statistics();
+        assert!(ordered.evaluated < unordered.evaluated);
+        assert!(ordered.nodes < unordered.nodes);
+    }
+
+    #[test]
+    fn test_principal_variation_starts_with_best_move() {
+        let board = Board::from_str("r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4").unwrap();
+        let mut search = Search::new(&board, Color::White);
+        let best = search.run(4, None, None);
+        let pv = search.principal_variation();
+        assert!(pv.len() >= 4);
+        assert_eq!(pv[0], best);
+        let mut position = board;
+        for mv in pv {
+            assert!(position.legal(mv));
+            position = position.make_move_new(mv);
+        }
+    }
+
+    #[test]
+    fn test_iteration_info_reports_mate() {
+        let board = Board::from_str("7k/7P/6PK/8/8/8/8/8 w - - 0 1").unwrap();
+        let mut search = Search::new(&board, Color::White);
+        search.run(2, None, None);
+        let info = search.iteration_info();
+        assert_eq!(info[0], UciInfoAttribute::Depth(2));
+        assert_eq!(info[2], UciInfoAttribute::from_mate(1));
+        assert!(matches!(info.last(), Some(UciInfoAttribute::Pv(pv)) if pv.len() == 1));
+    }
+
+    #[test]
+    fn test_shortest_mate_is_preferred() {
+        // Any pawn or king move mates soon, but only g7 mates at once
+        let board = Board::from_str("7k/7P/6PK/8/8/8/8/8 w - - 0 1").unwrap();
+        let mut search = Search::new(&board, Color::White);
+        let best = search.run(5, None, None);
+        assert_eq!(best.to_string(), "g6g7");
+        assert_eq!(search.score, CHECKMATE_EVAL - 1);
+    }
+
+    #[test]
+    fn test_mate_scores_from_table_count_plies_from_root() {
+        // The mating side keeps the table of an earlier search of the position after its
+        // first move, where every mate is a ply closer
+        let board = Board::from_str("6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1").unwrap();
+        let mut search = Search::new(&board, Color::White);
+        search.run(3, None, None);
+        assert_eq!(search.score, CHECKMATE_EVAL - 1);
+        let info = search.iteration_info();
+        assert_eq!(info[2], UciInfoAttribute::from_mate(1));
+
+        let table = Arc::clone(&search.table);
+        let after_wait = board.make_move_new(ChessMove::new(Square::G2, Square::G3, None));
+        let mut reply = Search::with_table(&after_wait, Color::Black, Arc::clone(&table));
+        reply.run(2, None, None);
+        let mut again = Search::with_table(&board, Color::White, table);
+        again.run(3, None, None);
+        assert_eq!(again.score, CHECKMATE_EVAL - 1);
+    }
+
+    /// A history in which the position after `mv` has already occurred twice.
+    fn history_repeating_after(board: &Board, mv: &str) -> GameHistory {
+        let repeated = board.make_move_new(ChessMove::from_str(mv).unwrap()).get_hash();
+        GameHistory {
+            hashes: vec![repeated, 1, repeated, 2, 3],
+            halfmove_clock: 5,
+        }
+    }
+
+    #[test]
+    fn test_losing_side_repeats_for_a_draw() {
+        let board = Board::from_str("6k1/2q5/8/8/8/8/8/6K1 w - - 5 10").unwrap();
+        let mut search = Search::new(&board, Color::White);
+        search.set_history(&history_repeating_after(&board, "g1h1"));
+        let best = search.run(3, None, None);
+        assert_eq!(best.to_string(), "g1h1");
+        assert_eq!(search.score, 0);
+    }
+
+    #[test]
+    fn test_contempt_decides_whether_to_repeat() {
+        let board = Board::from_str("6k1/p7/8/8/8/8/P7/6K1 w - - 5 10").unwrap();
+        let history = history_repeating_after(&board, "g1h1");
+        for (contempt, repeats) in [(-50, true), (50, false)] {
+            let mut search = Search::new(&board, Color::White);
+            search.set_options(SearchOptions {
+                contempt,
+                ..SearchOptions::default()
+            });
+            search.set_history(&history);
+            let best = search.run(3, None, None);
+            assert_eq!(best.to_string() == "g1h1", repeats, "contempt {}", contempt);
+            if repeats {
+                assert_eq!(search.score, -contempt);
+            }
+        }
+    }
+
+    #[test]
+    fn test_pondering_keeps_the_contempt_of_the_engine() {
+        // Pondering for white on black's move, black repeats when white wants no draw
+        let board = Board::from_str("6k1/p7/8/8/8/8/P7/6K1 b - - 5 10").unwrap();
+        let history = history_repeating_after(&board, "g8h8");
+        for (contempt, repeats) in [(50, true), (-50, false)] {
+            let mut search = Search::new(&board, Color::White);
+            search.set_options(SearchOptions {
+                contempt,
+                ..SearchOptions::default()
+            });
+            search.set_history(&history);
+            let best = search.run(3, None, None);
+            assert_eq!(best.to_string() == "g8h8", repeats, "contempt {}", contempt);
+            if repeats {
+                assert_eq!(search.score, contempt);
+            }
+        }
+    }
+
+    #[test]
+    fn test_stalemate_is_a_draw_with_contempt() {
+        let board = Board::from_str("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        assert_eq!(board.status(), BoardStatus::Stalemate);
+        for color in [Color::White, Color::Black] {
+            let mut search = Search::new(&board, color);
+            search.set_options(SearchOptions {
+                contempt: 30,
+                ..SearchOptions::default()
+            });
+            let expected = if color == Color::Black { -30 } else { 30 };
+            assert_eq!(search.score(1, -INFINITY, INFINITY), Some(expected));
+            assert_eq!(search.score(0, -INFINITY, INFINITY), Some(expected));
+        }
+    }
+
+    #[test]
+    fn test_fifty_move_rule_draws() {
+        let board = Board::from_str("6k1/2q5/8/8/8/8/8/6K1 w - - 99 80").unwrap();
+        let mut search = Search::new(&board, Color::White);
+        search.set_history(&GameHistory {
+            hashes: vec![],
+            halfmove_clock: 99,
+        });
+        search.run(3, None, None);
+        assert_eq!(search.score, 0);
+    }
+
+    #[test]
+    fn test_repetition_inside_the_tree_is_a_draw() {
+        let mut board = Game::new().current_position();
+        let mut search = Search::new(&board, Color::White);
+        for (i, mv) in ["g1f3", "g8f6", "f3g1", "f6g8"].iter().enumerate() {
+            assert!(!search.is_draw(&board), "after {} moves", i);
+            let mv = ChessMove::from_str(mv).unwrap();
+            let child = board.make_move_new(mv);
+            search.enter_position(&child, is_irreversible(&board, mv));
+            board = child;
+        }
+        assert!(search.is_draw(&board));
+    }
+
+    #[test]
+    fn test_game_history_needs_a_third_occurrence() {
+        let board = Game::new().current_position();
+        let mut history = GameHistory::default();
+        let mut position = board;
+        for mv in ["g1f3", "g8f6", "f3g1", "f6g8"] {
+            let mv = ChessMove::from_str(mv).unwrap();
+            history = history.after(&position, mv);
+            position = position.make_move_new(mv);
+        }
+        assert_eq!(position, board);
+        assert!(!history.is_draw(&position));
+        let mut twice = history.clone();
+        twice.hashes.extend_from_slice(&history.hashes);
+        twice.halfmove_clock = 8;
+        assert!(twice.is_draw(&position));
+        // A pawn move starts the history over
+        assert_eq!(history.after(&position, ChessMove::from_str("e2e4").unwrap()), GameHistory::default());
+    }
+
+    fn search_with_null_move(board: &Board, null_move: bool) -> Search {
+        let mut search = Search::new(board, board.side_to_move());
+        search.set_options(SearchOptions {
+            null_move,
+            ..SearchOptions::default()
+        });
+        search
+    }
+
+    #[test]
+    fn test_null_move_pruning_saves_nodes() {
+        let board = Board::from_str("r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4").unwrap();
+        let mut with = search_with_null_move(&board, true);
+        let mut without = search_with_null_move(&board, false);
+        assert_eq!(with.run(5, None, None), without.run(5, None, None));
+        assert!(with.statistics().null_cutoffs > 0);
+        assert_eq!(without.statistics().null_cutoffs, 0);
+        assert!(with.statistics().nodes < without.statistics().nodes);
+    }
+
+    #[test]
+    fn test_null_move_allowed_only_where_passing_is_safe() {
+        let allowed = |fen: &str, beta: i16| {
+            let board = Board::from_str(fen).unwrap();
+            let mut search = search_with_null_move(&board, true);
+            // Any move leads here, but not a pass
+            search.tree.add_child(Position::new(Some(ChessMove::default()), -INFINITY, INFINITY, 1));
+            search.tree.goto_last_child();
+            search.null_move_allowed(&board, 1, 4, beta)
+        };
+        assert!(allowed("4k3/8/8/8/8/8/3PPP2/3QK3 w - - 0 1", 0));
+        // In check
+        assert!(!allowed("4k3/8/8/8/8/8/3PrP2/3QK3 w - - 0 1", 0));
+        // Only pawns left
+        assert!(!allowed("4k3/8/8/8/8/8/3PPP2/4K3 w - - 0 1", -1000));
+        // Worse than beta already, or mates in the window
+        assert!(!allowed("4k3/8/8/8/8/8/3PPP2/3QK3 w - - 0 1", 2000));
+        assert!(!allowed("4k3/8/8/8/8/8/3PPP2/3QK3 w - - 0 1", -MATE_THRESHOLD - 1));
+
+        // Not twice in a row
+        let board = Board::from_str("4k3/8/8/8/8/8/3PPP2/3QK3 w - - 0 1").unwrap();
+        let mut search = search_with_null_move(&board, true);
+        search.tree.add_child(Position::new(None, -INFINITY, INFINITY, 1));
+        search.tree.goto_last_child();
+        assert!(!search.null_move_allowed(&board, 1, 4, 0));
+    }
+
+    #[test]
+    fn test_no_null_move_in_pawn_endgames() {
+        let board = Board::from_str("8/5k2/3p4/1p1Pp2p/pP2Pp1P/P4P1K/8/8 b - - 0 1").unwrap();
+        let mut search = search_with_null_move(&board, true);
+        search.run(6, None, None);
+        assert_eq!(search.statistics().null_cutoffs, 0);
+    }
+
+    #[test]
+    fn test_verification_finds_zugzwang() {
+        // Only Rf1 wins, by leaving black without a good move
+        let board = Board::from_str("8/8/p1p5/1p5p/1P5p/8/PPP2K1p/4R1rk w - - 0 1").unwrap();
+        let mut search = search_with_null_move(&board, true);
+        assert_eq!(search.run(10, None, None).to_string(), "e1f1");
+        assert!(search.statistics().null_cutoffs > 0);
+    }
+
+    #[test]
+    fn test_late_move_reductions_grow_with_depth_and_move_number() {
+        let board = Board::from_str("r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4").unwrap();
+        let search = Search::new(&board, Color::White);
+        let reduction = |mv: &str, depth_left, searched| {
+            let mv = ChessMove::from_str(mv).unwrap();
+            search.late_move_reduction(&board, mv, &board.make_move_new(mv), depth_left, searched)
+        };
+        assert_eq!(reduction("a2a3", 8, 2), 0);
+        assert_eq!(reduction("a2a3", 2, 20), 0);
+        assert_eq!(reduction("a2a3", 3, 3), 1);
+        assert!(reduction("a2a3", 12, 30) > reduction("a2a3", 6, 30));
+        assert!(reduction("a2a3", 12, 30) > reduction("a2a3", 12, 5));
+        // Captures are never reduced, checking or not
+        assert_eq!(reduction("f3e5", 12, 30), 0);
+        assert_eq!(reduction("c4f7", 12, 30), 0);
+        // At least one ply is left
+        assert_eq!(reduction("a2a3", 4, 40), 2);
+
+        let mut search = Search::new(&board, Color::White);
+        search.set_options(SearchOptions {
+            lmr_base: 0,
+            lmr_divisor: MAX_LMR_DIVISOR,
+            ..SearchOptions::default()
+        });
+        let mv = ChessMove::from_str("a2a3").unwrap();
+        assert_eq!(search.late_move_reduction(&board, mv, &board.make_move_new(mv), 8, 10), 0);
+    }
+
+    #[test]
+    fn test_late_move_reductions_save_nodes() {
+        let board = Board::from_str("2r2rk1/p1qnbppp/1p1ppn2/6N1/2PQ4/2N3P1/PP2PPKP/R1B2R2 w - - 3 14").unwrap();
+        let mut searches = [true, false].map(|late_move_reductions| {
+            let mut search = Search::new(&board, Color::White);
+            search.set_options(SearchOptions {
+                late_move_reductions,
+                ..SearchOptions::default()
+            });
+            search.run_timed(5, Duration::from_secs(600));
+            search
+        });
+        let [with, without] = &mut searches;
+        assert!(with.statistics().reduced > 0);
+        assert_eq!(without.statistics().reduced, 0);
+        assert!(with.statistics().nodes < without.statistics().nodes);
+        assert_eq!(with.completed_depth(), 5);
+    }
+
+    #[test]
+    fn test_run_searches_with_the_given_window() {
+        let board = Board::from_str("4k3/8/8/8/8/8/8/3QK3 w - - 0 1").unwrap();
+        let mut search = Search::new(&board, Color::White);
+        let best = search.run(3, None, None);
+        assert_eq!(search.score_bound, Bound::Exact);
+        let score = search.score;
+
+        // A queen up is far above the window
+        let mut high = Search::new(&board, Color::White);
+        high.run(3, Some(-100), Some(100));
+        assert_eq!(high.tree.root().data.beta, 100);
+        assert_eq!(high.score_bound, Bound::Lower);
+        assert!(high.score >= 100);
+        assert_eq!(
+            high.iteration_info()[2],
+            UciInfoAttribute::Score {
+                cp: Some(high.score as i32),
+                mate: None,
+                lower_bound: Some(true),
+                upper_bound: None
+            }
+        );
+
+        // And below this one, which leaves the best move as it was
+        assert_eq!(search.run(3, Some(score + 100), Some(score + 200)), best);
+        assert_eq!(search.score_bound, Bound::Upper);
+        assert!(search.score <= score + 100);
+    }
+
+    /// Searches to depth 3, and then to depth 4 with a window around the score of depth 3
+    /// moved by `shift`.
+    fn search_with_shifted_window(board: &Board, shift: i16) -> Search {
+        let mut search = Search::new(board, board.side_to_move());
+        search.run_timed(3, Duration::from_secs(600));
+        search.score += shift;
+        search.run_with_aspiration(4);
+        search
+    }
+
+    #[test]
+    fn test_aspiration_window_widens_after_failing_high() {
+        let board = Board::from_str("r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4").unwrap();
+        let mut full = Search::new(&board, Color::White);
+        let best = full.run(4, None, None);
+
+        let search = search_with_shifted_window(&board, -300);
+        assert!(search.statistics().failed_high > 0);
+        assert_eq!(search.statistics().failed_low, 0);
+        assert_eq!(search.score_bound, Bound::Exact);
+        assert_eq!(search.score, full.score);
+        assert_eq!(search.best_move, Some(best));
+    }
+
+    #[test]
+    fn test_aspiration_window_widens_after_failing_low() {
+        // Nxh4 is the only move that wins the queen, where the Italian has moves as good as
+        // one another
+        let board = Board::from_str("rnb1kbnr/pppp1ppp/8/4p3/4P2q/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3").unwrap();
+        let mut full = Search::new(&board, Color::White);
+        let best = full.run(4, None, None);
+
+        let search = search_with_shifted_window(&board, 300);
+        assert!(search.statistics().failed_low > 0);
+        assert_eq!(search.statistics().failed_high, 0);
+        assert_eq!(search.score_bound, Bound::Exact);
+        assert_eq!(search.score, full.score);
+        assert_eq!(search.best_move, Some(best));
+    }
+
+    #[test]
+    fn test_checks_only_moves_and_pawn_pushes_to_the_seventh_rank_are_extended() {
+        let board = Board::from_str("4k3/8/P7/8/8/8/8/R3K3 w - - 0 1").unwrap();
+        let mut search = Search::new(&board, Color::White);
+        search.current_depth = 4;
+        let extension = |search: &mut Search, mv: &str, only_move| {
+            let mv = ChessMove::from_str(mv).unwrap();
+            search.extension(&board, mv, &board.make_move_new(mv), only_move)
+        };
+        assert_eq!(extension(&mut search, "a1a8", false), 1);
+        assert_eq!(extension(&mut search, "a6a7", false), 1);
+        assert_eq!(extension(&mut search, "e1d1", true), 1);
+        assert_eq!(extension(&mut search, "e1d1", false), 0);
+        // A line is extended by at most the depth of the iteration
+        search.line_extensions = 4;
+        assert_eq!(extension(&mut search, "a1a8", false), 0);
+    }
+
+    #[test]
+    fn test_check_extensions_find_mates_beyond_the_depth() {
+        // Qxh8+ Kxh8 Bf6+ Kg8 Re8#, all checks and only moves
+        let board = Board::from_str("r1b3kr/ppp1Bp1p/1b6/n2P4/2p3q1/2Q2N2/P4PPP/RN2R1K1 w - - 1 1").unwrap();
+        let mut search = Search::new(&board, Color::White);
+        assert_eq!(search.run(3, None, None).to_string(), "c3h8");
+        assert_eq!(search.score, CHECKMATE_EVAL - 5);
+        assert!(search.seldepth() >= 5);
+        assert!(search.statistics().extended > 0);
+
+        let mut without = Search::new(&board, Color::White);
+        without.set_options(SearchOptions {
+            extensions: false,
+            ..SearchOptions::default()
+        });
+        without.run(3, None, None);
+        assert!(!is_mate_score(without.score));
+    }
+
+    fn search_with_pruning(board: &Board, futility_pruning: bool, reverse_futility_pruning: bool, razoring: bool) -> Search {
+        let mut search = Search::new(board, board.side_to_move());
+        search.set_options(SearchOptions {
+            futility_pruning,
+            reverse_futility_pruning,
+            razoring,
+            ..SearchOptions::default()
+        });
+        search
+    }
+
+    #[test]
+    fn test_no_pruning_by_static_eval_in_check_or_near_mate() {
+        let near_leaves = |fen: &str, ply: usize, alpha: i16, beta: i16| {
+            let board = Board::from_str(fen).unwrap();
+            search_with_pruning(&board, true, true, true).static_eval_near_leaves(&board, ply, 2, alpha, beta)
+        };
+        let quiet = "4k3/8/8/8/8/8/3PPP2/3QK3 w - - 0 1";
+        assert_eq!(near_leaves(quiet, 1, -INFINITY, INFINITY), Some(1200));
+        // At the root, in check, or with a mate score in the window
+        assert_eq!(near_leaves(quiet, 0, -INFINITY, INFINITY), None);
+        assert_eq!(near_leaves("4k3/8/8/8/8/8/3PrP2/3QK3 w - - 0 1", 1, -INFINITY, INFINITY), None);
+        assert_eq!(near_leaves(quiet, 1, MATE_THRESHOLD + 1, INFINITY), None);
+        assert_eq!(near_leaves(quiet, 1, -INFINITY, -MATE_THRESHOLD - 1), None);
+
+        // Nor any quiet move pruned once alpha is a mate score
+        let board = Board::from_str(quiet).unwrap();
+        let search = search_with_pruning(&board, true, true, true);
+        let mv = ChessMove::from_str("d1b3").unwrap();
+        let child = board.make_move_new(mv);
+        assert!(search.is_futile(&board, mv, &child, 1, 1500, Some(1200)));
+        assert!(!search.is_futile(&board, mv, &child, 1, MATE_THRESHOLD + 1, Some(1200)));
+        assert!(!search.is_futile(&board, mv, &child, 1, 1500, None));
+        // Checks are searched
+        let mv = ChessMove::from_str("d1a4").unwrap();
+        assert!(!search.is_futile(&board, mv, &board.make_move_new(mv), 1, 1500, Some(1200)));
+    }
+
+    #[test]
+    fn test_each_pruning_near_the_leaves_has_its_switch() {
+        let board = Board::from_str("r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4").unwrap();
+        let mut none = search_with_pruning(&board, false, false, false);
+        none.run(5, None, None);
+        let statistics = none.statistics();
+        assert_eq!(statistics.futility_pruned + statistics.reverse_futility_cutoffs + statistics.razored, 0);
+
+        let mut futility = search_with_pruning(&board, true, false, false);
+        futility.run(5, None, None);
+        assert!(futility.statistics().futility_pruned > 0);
+        assert_eq!(futility.statistics().reverse_futility_cutoffs + futility.statistics().razored, 0);
+
+        let mut reverse_futility = search_with_pruning(&board, false, true, false);
+        reverse_futility.run(5, None, None);
+        assert!(reverse_futility.statistics().reverse_futility_cutoffs > 0);
+        assert_eq!(reverse_futility.statistics().futility_pruned + reverse_futility.statistics().razored, 0);
+
+        let mut razoring = search_with_pruning(&board, false, false, true);
+        razoring.run(5, None, None);
+        assert!(razoring.statistics().razored > 0);
+        assert_eq!(razoring.statistics().futility_pruned + razoring.statistics().reverse_futility_cutoffs, 0);
+
+        let mut all = search_with_pruning(&board, true, true, true);
+        all.run(5, None, None);
+        assert!(all.statistics().nodes < none.statistics().nodes);
+    }
+
+    #[test]
+    fn test_razoring_keeps_nodes_with_quiet_checks() {
+        // Rd8 and Rd1#, while promoting a pawn wins more material at depth 3, so the node
+        // after Rd8 is far enough below alpha to be razored but for the check
+        let board = Board::from_str("4r2k/4p3/1p6/q1p5/1Pp5/pPP2pPp/P4P1P/7K b - - 0 1").unwrap();
+        let mut search = search_with_pruning(&board, true, true, true);
+        assert_eq!(search.run(3, None, None).to_string(), "e8d8");
+        assert_eq!(search.score, CHECKMATE_EVAL - 3);
+        assert!(search.statistics().razored > 0);
+    }
+
+    fn search_with_predicted_cutoffs(board: &Board, probcut: bool, multi_cut: bool) -> Search {
+        let mut search = Search::new(board, board.side_to_move());
+        search.set_options(SearchOptions {
+            probcut,
+            multi_cut,
+            ..SearchOptions::default()
+        });
+        search
+    }
+
+    #[test]
+    fn test_cutoffs_predicted_only_at_null_windows_out_of_check_and_mate() {
+        let predicted = |fen: &str, alpha: i16, beta: i16| {
+            let board = Board::from_str(fen).unwrap();
+            let mut search = search_with_predicted_cutoffs(&board, true, true);
+            search.tree.current_mut().data.potential_next_moves = Some(MoveGen::new_legal(&board).collect());
+            // Any move leads here
+            search.line = vec![ChessMove::default()];
+            search.predict_cutoff(&board, 6, alpha, beta)
+        };
+        // The queen on d5 hangs
+        let hanging_queen = "4k3/8/8/3q4/8/2N5/3PPP2/4K3 w - - 0 1";
+        assert_eq!(predicted(hanging_queen, -1, 0), Some(Prediction::ProbCut));
+        assert_eq!(predicted(hanging_queen, -100, 0), None);
+        assert_eq!(predicted(hanging_queen, MATE_THRESHOLD, MATE_THRESHOLD + 1), None);
+        assert_eq!(predicted("4k3/8/8/3q4/8/2N5/3PrP2/4K3 w - - 0 1", -1, 0), None);
+    }
+
+    #[test]
+    fn test_probcut_searches_captures_good_enough_once() {
+        let board = Board::from_str("4k3/8/8/3q4/8/2N5/3PPP2/4K3 w - - 0 1").unwrap();
+        let mut search = search_with_predicted_cutoffs(&board, true, false);
+        let legal_moves: Vec<ChessMove> = MoveGen::new_legal(&board).collect();
+        search.tree.current_mut().data.potential_next_moves = Some(legal_moves.clone());
+        search.line = vec![ChessMove::default()];
+        // Winning the queen is not enough for a beta a queen and a pawn up, so nothing is
+        // searched
+        assert!(!search.probcut(&board, 6, 900));
+        assert_eq!(search.statistics().nodes, 0);
+        assert!(search.probcut(&board, 6, 0));
+        assert!(search.statistics().nodes > 0);
+        // The capture now has its child, and is no longer among the moves to search
+        assert_eq!(search.tree.number_of_children(), 1);
+        let next_moves = search.tree.current().data.potential_next_moves.as_ref().unwrap();
+        assert_eq!(next_moves.len(), legal_moves.len() - 1);
+        assert!(!next_moves.contains(&ChessMove::from_str("c3d5").unwrap()));
+    }
+
+    #[test]
+    fn test_predicted_cutoffs_are_counted_and_checked() {
+        let board = Board::from_str("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+        let mut search = search_with_predicted_cutoffs(&board, true, true);
+        search.run(7, None, None);
+        let statistics = search.statistics();
+        // The first prediction of each kind is checked
+        assert!(statistics.probcut_checked > 0);
+        assert!(statistics.probcut_cutoffs > 0);
+        assert!(statistics.probcut_right <= statistics.probcut_checked);
+        assert!(statistics.multi_cut_checked > 0);
+        assert!(statistics.multi_cut_right <= statistics.multi_cut_checked);
+
+        let mut without = search_with_predicted_cutoffs(&board, false, false);
+        without.run(7, None, None);
+        let statistics = without.statistics();
+        assert_eq!(statistics.probcut_cutoffs + statistics.probcut_checked, 0);
+        assert_eq!(statistics.multi_cut_cutoffs + statistics.multi_cut_checked, 0);
+    }
+
+    #[test]
+    fn test_table_is_reused_between_searches() {
+        let board = Game::new().current_position();
+        let mut first = Search::new(&board, Color::White);
+        let best = first.run(3, None, None);
+        let evaluated = first.number_of_evaluated;
+
+        let mut second = Search::with_table(&board, Color::White, Arc::clone(&first.table));
+        assert_eq!(second.run(3, None, None), best);
+        assert!(second.number_of_evaluated < evaluated);
+        assert!(second.number_of_table_cutoffs > 0);
+    }
+
+    #[test]
+    fn test_quiescence_does_not_take_defended_pawn_with_queen() {
+        let board = Board::from_str("4k3/8/4p3/3p4/8/8/8/3QK3 w - - 0 1").unwrap();
+        let mut search = Search::new(&board, Color::White);
+        assert_eq!(search.quiescence(&board, -INFINITY, INFINITY, 0), 700);
+        assert_mv_ne(&search.run(1, None, None), "d1:d5");
+    }
+
+    #[test]
+    fn test_quiescence_resolves_exchanges() {
+        // The rook can take the undefended pawn, but the pawn on d6 is defended
+        let board = Board::from_str("4k3/4p3/3p4/8/1p6/8/8/1R1RK3 w - - 0 1").unwrap();
+        let mut search = Search::new(&board, Color::White);
+        assert_eq!(search.quiescence(&board, -INFINITY, INFINITY, 0), 800);
+    }
+
+    #[test]
+    fn test_quiescence_recognizes_checkmate() {
+        let board = Board::from_str("7k/6Q1/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        let mut search = Search::new(&board, Color::Black);
+        assert_eq!(search.quiescence(&board, -INFINITY, INFINITY, 0), -10000);
+    }
+
+    #[test]
+    fn test_quiescence_searches_quiet_checks_only_with_the_option() {
+        // Rd1 mates, but it is no capture
+        let board = Board::from_str("3r2k1/8/8/8/8/8/5PPP/6K1 b - - 0 1").unwrap();
+        let mut search = Search::new(&board, Color::Black);
+        assert_eq!(search.quiescence(&board, -INFINITY, INFINITY, 0), CHECKMATE_EVAL - 1);
+        search.set_options(SearchOptions {
+            quiet_checks: false,
+            ..SearchOptions::default()
+        });
+        assert_eq!(search.quiescence(&board, -INFINITY, INFINITY, 0), 200);
+    }
+
+    const PIECES: [Piece; 5] = [Piece::Queen, Piece::Rook, Piece::Bishop, Piece::Knight, Piece::Pawn];
+
+    /// Legal positions with both kings and up to six other pieces, which shrink to positions
+    /// with fewer pieces. The chess crate takes pawns on the first and last rank for legal.
+    fn arbitrary_board() -> impl Strategy<Value = Board> {
+        let piece = (0..PIECES.len(), any::<bool>(), 0..64usize);
+        (0..64usize, 0..64usize, prop::collection::vec(piece, 0..=6), any::<bool>()).prop_filter_map(
+            "not a legal position",
+            |(white_king, black_king, pieces, white_to_move)| {
+                // Updating the pins of a board without a king goes out of bounds
+                if white_king == black_king {
+                    return None;
+                }
+                let mut builder = BoardBuilder::new();
+                for (piece, white, square) in pieces {
+                    if PIECES[piece] == Piece::Pawn && !(8..56).contains(&square) {
+                        return None;
+                    }
+                    let color = if white { Color::White } else { Color::Black };
+                    builder.piece(ALL_SQUARES[square], PIECES[piece], color);
+                }
+                builder
+                    .piece(ALL_SQUARES[white_king], Piece::King, Color::White)
+                    .piece(ALL_SQUARES[black_king], Piece::King, Color::Black)
+                    .side_to_move(if white_to_move { Color::White } else { Color::Black });
+                Board::try_from(builder).ok().filter(|board| board.status() == BoardStatus::Ongoing)
+            },
+        )
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(64))]
+
+        /// `Search::run` against the minimax value of a search without any pruning, which
+        /// is what alpha-beta, the table and the move ordering must all preserve. Extensions,
+        /// quiet checks and the pruning near the leaves change the value on purpose, so they
+        /// are left out.
+        #[test]
+        fn test_search_agrees_with_unpruned_negamax(board in arbitrary_board(), depth in 1..=3u8) {
+            let reference = minimax::negamax(&ChessGame::new(board), depth as u32);
+            let mut search = Search::new(&board, board.side_to_move());
+            search.set_options(SearchOptions {
+                extensions: false,
+                quiet_checks: false,
+                futility_pruning: false,
+                reverse_futility_pruning: false,
+                razoring: false,
+                ..SearchOptions::default()
+            });
+            let best = search.run(depth, None, None);
+            let best_value = -minimax::negamax(&ChessGame::at_ply(board.make_move_new(best), 1), depth as u32 - 1);
+            prop_assert_eq!(search.score as i32, reference, "score at depth {} of {}", depth, board);
+            prop_assert_eq!(best_value, reference, "value of {} at depth {} of {}", best, depth, board);
+        }
+
+        /// `Search::run` against `minimax::alpha_beta` on the same position. Both are the
+        /// search of the library, with everything the hooks of `Search` add to it turned off
+        /// but the tree, the table and the move ordering, which must not change its value.
+        #[test]
+        fn test_search_agrees_with_library_alpha_beta(board in arbitrary_board(), depth in 1..=3u8) {
+            let game = ChessGame::new(board);
+            let reference = minimax::alpha_beta(&game, depth as u32, -minimax::INFINITY, minimax::INFINITY, &mut ());
+            let mut search = Search::new(&board, board.side_to_move());
+            search.set_options(SearchOptions {
+                null_move: false,
+                late_move_reductions: false,
+                extensions: false,
+                quiet_checks: false,
+                futility_pruning: false,
+                reverse_futility_pruning: false,
+                razoring: false,
+                probcut: false,
+                multi_cut: false,
+                ..SearchOptions::default()
+            });
+            search.run(depth, None, None);
+            prop_assert_eq!(Some(search.score as i32), reference, "score at depth {} of {}", depth, board);
+        }
+    }
+
     #[test]
     fn test_avoiding_checkmate_in_one_white() {
         let textboard = r#"
@@ -539,7 +2700,8 @@ mod tests {
     }
 
     #[test]
-    fn test_wanting_checkmate_in_two_black_white_starts() {
+    fn test_wanting_checkmate_in_three_black_past_the_depth() {
+        // Rd8, and after Nd3 Rxd3 Rd1 still mates, a ply past the depth of the search
         let textboard = r#"
         8|   |   |   |   | ♖ |   |   | ♔ |
         7|   |   |   |   | ♙ |   |   |   |
@@ -555,11 +2717,20 @@ mod tests {
             textboard,
             CastleRights::NoRights,
             CastleRights::NoRights,
-            Color::White,
+            Color::Black,
         );
-        let mut search = Search::new(&board, Color::White);
+        let mut search = Search::new(&board, Color::Black);
         let best = search.run(4, None, None);
-        assert_mv_eq(&best, "e8:d8")
+        assert_mv_eq(&best, "e8:d8");
+        assert_eq!(search.score, CHECKMATE_EVAL - 5);
+
+        let mut without = Search::new(&board, Color::Black);
+        without.set_options(SearchOptions {
+            quiet_checks: false,
+            ..SearchOptions::default()
+        });
+        without.run(4, None, None);
+        assert!(!is_mate_score(without.score));
     }
 
     #[test]
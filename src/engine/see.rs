@@ -0,0 +1,149 @@
+use chess::{
+    get_bishop_moves, get_king_moves, get_knight_moves, get_pawn_attacks, get_rook_moves, BitBoard, Board, ChessMove,
+    Color, Piece, Square, EMPTY,
+};
+
+use crate::engine::eval::piece_value;
+
+const PIECES_BY_VALUE: [Piece; 6] = [
+    Piece::Pawn,
+    Piece::Knight,
+    Piece::Bishop,
+    Piece::Rook,
+    Piece::Queen,
+    Piece::King,
+];
+
+/// Static exchange evaluation: the material the side to move wins with `mv`, when both
+/// sides then keep capturing on its destination with their least valuable attacker, each
+/// stopping as soon as going on would lose more. Attackers behind others on the same line
+/// (x-rays) join in once the pieces in front of them have been exchanged. The king only
+/// captures a piece nothing defends anymore. Pins are not taken into account.
+pub fn see(board: &Board, mv: ChessMove) -> i16 {
+    let target = mv.get_dest();
+    let Some(mut on_target) = board.piece_on(mv.get_source()) else {
+        return 0;
+    };
+    let mut occupied = *board.combined() ^ BitBoard::from_square(mv.get_source());
+    // Every capture swaps one piece off the board, so there are no more of them than pieces
+    let mut gains = [0; 32];
+    gains[0] = match board.piece_on(target) {
+        Some(piece) => piece_value(piece),
+        None if on_target == Piece::Pawn && mv.get_source().get_file() != target.get_file() => {
+            occupied ^= BitBoard::from_square(Square::make_square(mv.get_source().get_rank(), target.get_file()));
+            piece_value(Piece::Pawn)
+        }
+        None => 0,
+    };
+    if let Some(promotion) = mv.get_promotion() {
+        gains[0] += piece_value(promotion) - piece_value(Piece::Pawn);
+        on_target = promotion;
+    }
+
+    let mut color = !board.side_to_move();
+    let mut depth = 0;
+    loop {
+        let attackers = attackers(board, target, occupied);
+        let Some((square, piece)) = least_valuable_attacker(board, attackers & *board.color_combined(color)) else {
+            break;
+        };
+        if piece == Piece::King && attackers & *board.color_combined(!color) != EMPTY {
+            break;
+        }
+        depth += 1;
+        gains[depth] = piece_value(on_target) - gains[depth - 1];
+        occupied ^= BitBoard::from_square(square);
+        on_target = piece;
+        color = !color;
+    }
+    // Going back to the first capture, each side only captures if that wins more than it
+    // had by stopping before
+    while depth > 0 {
+        gains[depth - 1] = -(-gains[depth - 1]).max(gains[depth]);
+        depth -= 1;
+    }
+    gains[0]
+}
+
+/// Pieces of both colors among `occupied` attacking `square`, looking through the pieces
+/// no longer in `occupied`.
+fn attackers(board: &Board, square: Square, occupied: BitBoard) -> BitBoard {
+    let pawns = *board.pieces(Piece::Pawn);
+    let diagonal = *board.pieces(Piece::Bishop) | *board.pieces(Piece::Queen);
+    let straight = *board.pieces(Piece::Rook) | *board.pieces(Piece::Queen);
+    let attackers = get_pawn_attacks(square, Color::Black, pawns & *board.color_combined(Color::White))
+        | get_pawn_attacks(square, Color::White, pawns & *board.color_combined(Color::Black))
+        | get_knight_moves(square) & *board.pieces(Piece::Knight)
+        | get_king_moves(square) & *board.pieces(Piece::King)
+        | get_bishop_moves(square, occupied) & diagonal
+        | get_rook_moves(square, occupied) & straight;
+    attackers & occupied
+}
+
+fn least_valuable_attacker(board: &Board, attackers: BitBoard) -> Option<(Square, Piece)> {
+    PIECES_BY_VALUE.iter().find_map(|&piece| {
+        let attackers = attackers & *board.pieces(piece);
+        (attackers != EMPTY).then(|| (attackers.to_square(), piece))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn see_of(fen: &str, mv: &str) -> i16 {
+        let board = Board::from_str(fen).unwrap();
+        let mv = ChessMove::from_str(mv).unwrap();
+        assert!(board.legal(mv), "{} in {}", mv, fen);
+        see(&board, mv)
+    }
+
+    #[test]
+    fn test_undefended_piece_is_won() {
+        assert_eq!(see_of("1k1r4/1pp4p/p7/4p3/8/P5P1/1PP4P/2K1R3 w - - 0 1", "e1e5"), 100);
+        assert_eq!(see_of("4k3/8/8/3q4/8/2N5/8/4K3 w - - 0 1", "c3d5"), 900);
+    }
+
+    #[test]
+    fn test_defended_pawn_costs_the_knight() {
+        // Nxe5 Nxe5 and White is better off stopping there than going on with Rxe5 Bxe5,
+        // with both queens behind
+        assert_eq!(see_of("1k1r3q/1ppn3p/p4b2/4p3/8/P2N2P1/1PP1R1BP/2K1Q3 w - - 0 1", "d3e5"), 100 - 290);
+    }
+
+    #[test]
+    fn test_x_rays_join_the_exchange() {
+        // The second rook takes back behind the first
+        assert_eq!(see_of("3rk3/8/8/3p4/8/8/3R4/3RK3 w - - 0 1", "d2d5"), 100);
+        assert_eq!(see_of("3rk3/8/8/3p4/8/8/3R4/4K3 w - - 0 1", "d2d5"), 100 - 500);
+        // After Rxe5 Bxe5 Rxe5, the queen behind the bishop takes back too
+        assert_eq!(see_of("4k3/8/5b2/4p3/8/8/4R3/4R2K w - - 0 1", "e2e5"), 100 - 500 + 310);
+        assert_eq!(see_of("4k3/6q1/5b2/4p3/8/8/4R3/4R2K w - - 0 1", "e2e5"), 100 - 500);
+    }
+
+    #[test]
+    fn test_king_only_takes_undefended_pieces() {
+        assert_eq!(see_of("8/8/4k3/3p4/8/8/3Q4/4K3 w - - 0 1", "d2d5"), 100 - 900);
+        // The rook behind the queen defends d5
+        assert_eq!(see_of("8/8/4k3/3p4/8/8/3Q4/3RK3 w - - 0 1", "d2d5"), 100);
+    }
+
+    #[test]
+    fn test_en_passant_and_promotions() {
+        // The pawn taken en passant is not on the destination, and once it is gone the rook
+        // on d1 defends d6
+        assert_eq!(see_of("3rk3/8/8/3pP3/8/8/8/3RK3 w - d6 0 1", "e5d6"), 100);
+        // A queen promoted where the rook takes it loses the pawn, unless the other rook
+        // takes back
+        assert_eq!(see_of("1r2k3/P7/8/8/8/8/8/4K3 w - - 0 1", "a7a8q"), -100);
+        assert_eq!(see_of("1r2k3/P7/8/8/8/8/8/R3K3 w - - 0 1", "a7a8q"), 400);
+        assert_eq!(see_of("1r2k3/P7/8/8/8/8/8/4K3 w - - 0 1", "a7b8q"), 500 + 900 - 100);
+    }
+
+    #[test]
+    fn test_quiet_move_to_an_attacked_square_loses_the_piece() {
+        assert_eq!(see_of("4k3/8/8/3p4/8/2N5/8/4K3 w - - 0 1", "c3b5"), 0);
+        assert_eq!(see_of("4k3/8/2p5/8/8/2N5/8/4K3 w - - 0 1", "c3b5"), -290);
+    }
+}
@@ -0,0 +1,273 @@
+use chess::{ChessMove, Piece, ALL_SQUARES};
+#[cfg(test)]
+use chess::Square;
+use std::mem::size_of;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+pub const DEFAULT_HASH_SIZE_MB: usize = 16;
+pub const MAX_HASH_SIZE_MB: usize = 4096;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Bound {
+    Exact,
+    // The real score is at least the stored one (the search failed high)
+    Lower,
+    // The real score is at most the stored one (the search failed low)
+    Upper,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Entry {
+    pub hash: u64,
+    pub best_move: Option<ChessMove>,
+    pub score: i16,
+    pub depth: u8,
+    pub bound: Bound,
+}
+
+impl Entry {
+    /// Whether the stored score alone decides the result of a search with the given window.
+    pub fn cuts_off(&self, alpha: i16, beta: i16) -> bool {
+        match self.bound {
+            Bound::Exact => true,
+            Bound::Lower => self.score >= beta,
+            Bound::Upper => self.score <= alpha,
+        }
+    }
+}
+
+/// Fixed size hash table of search results, indexed by `Board::get_hash`.
+///
+/// The table is shared by all search threads without locking. Every slot holds the packed
+/// entry and the hash xor-ed with it, so an entry torn by two threads writing at once no
+/// longer matches its hash and is simply treated as a miss.
+pub struct TranspositionTable {
+    slots: Vec<Slot>,
+}
+
+#[derive(Default)]
+struct Slot {
+    key: AtomicU64,
+    data: AtomicU64,
+}
+
+impl Default for TranspositionTable {
+    fn default() -> Self {
+        TranspositionTable::new(DEFAULT_HASH_SIZE_MB)
+    }
+}
+
+impl TranspositionTable {
+    pub fn new(size_mb: usize) -> TranspositionTable {
+        let number_of_slots = size_mb.min(MAX_HASH_SIZE_MB) * 1024 * 1024 / size_of::<Slot>();
+        TranspositionTable {
+            slots: (0..number_of_slots.max(1)).map(|_| Slot::default()).collect(),
+        }
+    }
+
+    pub fn probe(&self, hash: u64) -> Option<Entry> {
+        let slot = &self.slots[self.index(hash)];
+        let data = slot.data.load(Ordering::Relaxed);
+        if data != 0 && slot.key.load(Ordering::Relaxed) ^ data == hash {
+            Some(unpack(hash, data))
+        } else {
+            None
+        }
+    }
+
+    /// Stores the entry, unless its slot holds a deeper result of the same position.
+    pub fn store(&self, entry: Entry) {
+        if let Some(old) = self.probe(entry.hash) {
+            if old.depth > entry.depth {
+                return;
+            }
+        }
+        let slot = &self.slots[self.index(entry.hash)];
+        let data = pack(&entry);
+        slot.key.store(entry.hash ^ data, Ordering::Relaxed);
+        slot.data.store(data, Ordering::Relaxed);
+    }
+
+    pub fn clear(&self) {
+        for slot in self.slots.iter() {
+            slot.key.store(0, Ordering::Relaxed);
+            slot.data.store(0, Ordering::Relaxed);
+        }
+    }
+
+    /// How full the table is in permille, estimated from the first thousand entries.
+    pub fn hashfull(&self) -> u16 {
+        let sample = self.slots.len().min(1000);
+        let used = self.slots[..sample]
+            .iter()
+            .filter(|slot| slot.data.load(Ordering::Relaxed) != 0)
+            .count();
+        (used * 1000 / sample) as u16
+    }
+
+    fn index(&self, hash: u64) -> usize {
+        (hash % self.slots.len() as u64) as usize
+    }
+}
+
+// Layout of the packed entry, from the lowest bit:
+// 16 bits move, 16 bits score, 8 bits depth, 2 bits bound (never 0, so no entry packs to 0)
+fn pack(entry: &Entry) -> u64 {
+    let bound = match entry.bound {
+        Bound::Exact => 1,
+        Bound::Lower => 2,
+        Bound::Upper => 3,
+    };
+    pack_move(entry.best_move) as u64
+        | (entry.score as u16 as u64) << 16
+        | (entry.depth as u64) << 32
+        | bound << 40
+}
+
+fn unpack(hash: u64, data: u64) -> Entry {
+    Entry {
+        hash,
+        best_move: unpack_move(data as u16),
+        score: (data >> 16) as u16 as i16,
+        depth: (data >> 32) as u8,
+        bound: match (data >> 40) & 3 {
+            1 => Bound::Exact,
+            2 => Bound::Lower,
+            _ => Bound::Upper,
+        },
+    }
+}
+
+const PROMOTIONS: [Piece; 4] = [Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen];
+const HAS_MOVE: u16 = 1 << 15;
+
+fn pack_move(mv: Option<ChessMove>) -> u16 {
+    match mv {
+        Some(mv) => {
+            let promotion = match mv.get_promotion() {
+                Some(piece) => PROMOTIONS.iter().position(|p| *p == piece).unwrap() as u16 + 1,
+                None => 0,
+            };
+            HAS_MOVE
+                | mv.get_source().to_index() as u16
+                | (mv.get_dest().to_index() as u16) << 6
+                | promotion << 12
+        }
+        None => 0,
+    }
+}
+
+fn unpack_move(packed: u16) -> Option<ChessMove> {
+    if packed & HAS_MOVE == 0 {
+        return None;
+    }
+    let source = ALL_SQUARES[(packed & 63) as usize];
+    let dest = ALL_SQUARES[((packed >> 6) & 63) as usize];
+    let promotion = match (packed >> 12) & 7 {
+        0 => None,
+        piece => Some(PROMOTIONS[piece as usize - 1]),
+    };
+    Some(ChessMove::new(source, dest, promotion))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+
+    fn entry(hash: u64, depth: u8, score: i16) -> Entry {
+        Entry {
+            hash,
+            best_move: Some(ChessMove::new(Square::E2, Square::E4, None)),
+            score,
+            depth,
+            bound: Bound::Exact,
+        }
+    }
+
+    #[test]
+    fn test_storing_and_probing() {
+        let table = TranspositionTable::new(1);
+        table.store(entry(12345, 3, 42));
+        let found = table.probe(12345).unwrap();
+        assert_eq!(found.score, 42);
+        assert_eq!(found.depth, 3);
+        assert!(table.probe(54321).is_none());
+    }
+
+    #[test]
+    fn test_deeper_entry_of_same_position_is_kept() {
+        let table = TranspositionTable::new(1);
+        table.store(entry(12345, 5, 42));
+        table.store(entry(12345, 2, 7));
+        assert_eq!(table.probe(12345).unwrap().score, 42);
+        table.store(entry(12345, 6, 7));
+        assert_eq!(table.probe(12345).unwrap().score, 7);
+    }
+
+    #[test]
+    fn test_packing_keeps_every_field() {
+        let table = TranspositionTable::new(1);
+        let promotion = ChessMove::new(Square::B7, Square::A8, Some(Piece::Knight));
+        for (best_move, score, bound) in [
+            (Some(promotion), -10000, Bound::Lower),
+            (None, 10000, Bound::Upper),
+            (Some(ChessMove::new(Square::H1, Square::A8, None)), 0, Bound::Exact),
+        ] {
+            table.store(Entry {
+                hash: 987654321,
+                best_move,
+                score,
+                depth: 200,
+                bound,
+            });
+            let found = table.probe(987654321).unwrap();
+            assert_eq!(found.best_move, best_move);
+            assert_eq!(found.score, score);
+            assert_eq!(found.depth, 200);
+            assert_eq!(found.bound, bound);
+        }
+    }
+
+    #[test]
+    fn test_sharing_between_threads() {
+        let table = Arc::new(TranspositionTable::new(1));
+        let handles: Vec<_> = (0..4u64)
+            .map(|i| {
+                let table = Arc::clone(&table);
+                thread::spawn(move || {
+                    for hash in (i * 1000)..(i * 1000 + 1000) {
+                        table.store(entry(hash, 1, hash as i16));
+                    }
+                })
+            })
+            .collect();
+        handles.into_iter().for_each(|handle| handle.join().unwrap());
+        for hash in 0..4000 {
+            assert_eq!(table.probe(hash).unwrap().score, hash as i16);
+        }
+    }
+
+    #[test]
+    fn test_bounds_cutting_off() {
+        let mut lower = entry(1, 1, 50);
+        lower.bound = Bound::Lower;
+        assert!(lower.cuts_off(0, 50));
+        assert!(!lower.cuts_off(0, 51));
+        let mut upper = entry(1, 1, 50);
+        upper.bound = Bound::Upper;
+        assert!(upper.cuts_off(50, 100));
+        assert!(!upper.cuts_off(49, 100));
+        assert!(entry(1, 1, 50).cuts_off(100, 200));
+    }
+
+    #[test]
+    fn test_clearing() {
+        let table = TranspositionTable::new(1);
+        table.store(entry(12345, 3, 42));
+        table.clear();
+        assert!(table.probe(12345).is_none());
+        assert_eq!(table.hashfull(), 0);
+    }
+}
@@ -1,13 +1,13 @@
-use std::cell::RefCell;
-use std::rc::{Rc, Weak};
-use core::slice::Iter;
+use std::mem::size_of;
 
 #[derive(Debug)]
 pub struct Node<T> {
     pub data: T,
-    children: Vec<Rc<RefCell<Node<T>>>>,
-    parent: Option<Weak<RefCell<Node<T>>>>,
-    pub index: Option<usize>
+    children: Vec<usize>,
+    parent: Option<usize>,
+    pub index: Option<usize>,
+    // Memory the data allocates itself, as far as the tree was told
+    heap_size: usize,
 }
 
 impl<T> Node<T> {
@@ -15,46 +15,149 @@ impl<T> Node<T> {
         Node::new_with_parent(data, None, None)
     }
 
-    pub fn new_with_parent(data: T, parent: Option<Weak<RefCell<Node<T>>>>, index: Option<usize>) -> Node<T> {
-        return Node {
-            data: data,
+    pub fn new_with_parent(data: T, parent: Option<usize>, index: Option<usize>) -> Node<T> {
+        Node {
+            data,
             children: vec![],
-            parent: parent,
-            index: index,
-        };
-    }
-
-    pub fn add_child(&mut self, child: Rc<RefCell<Node<T>>>) {
-        self.children.push(Rc::clone(&child));
+            parent,
+            index,
+            heap_size: 0,
+        }
     }
 }
 
+/// A tree with a cursor, whose nodes live in one arena and refer to each other by their
+/// position in it.
+///
+/// Freed nodes leave their slot to the next added node, so a tree whose subtrees are freed
+/// and grown again does not allocate. The tree can be given a budget of nodes or of memory,
+/// over which no more children are added. What the data of a node allocates itself only
+/// counts towards the memory once the tree is told with `set_heap_size`.
 #[derive(Debug)]
 pub struct Tree<T> {
-    pub root: Rc<RefCell<Node<T>>>,
-    pub current: Rc<RefCell<Node<T>>>,
+    nodes: Vec<Option<Node<T>>>,
+    free_slots: Vec<usize>,
+    root: usize,
+    current: usize,
+    node_budget: Option<usize>,
+    memory_budget: Option<usize>,
+    heap_size: usize,
 }
 
 impl<T> Tree<T> {
+    /// Memory taken by a node, including its entry in the children of its parent.
+    pub const NODE_SIZE: usize = size_of::<Option<Node<T>>>() + size_of::<usize>();
+
     pub fn new(data: T) -> Tree<T> {
-        let root = Rc::new(RefCell::new(Node::new(data)));
         Tree {
-            root: Rc::clone(&root),
-            current: Rc::clone(&root),
+            nodes: vec![Some(Node::new(data))],
+            free_slots: vec![],
+            root: 0,
+            current: 0,
+            node_budget: None,
+            memory_budget: None,
+            heap_size: 0,
         }
     }
 
-    pub fn add_child(&self, data: T) {
-        let child = Rc::new(RefCell::new(Node::new_with_parent(
-            data,
-            Some(Rc::downgrade(&Rc::clone(&self.current))),
-            Some(self.number_of_children())
-        )));
-        self.current.borrow_mut().add_child(child);
+    /// Limits the tree to the given number of nodes, or lifts the limit with `None`.
+    #[cfg(test)]
+    pub fn set_node_budget(&mut self, node_budget: Option<usize>) {
+        self.node_budget = node_budget;
+    }
+
+    /// Limits the tree to the given number of bytes, counting its nodes and the heap sizes
+    /// set for their data.
+    pub fn set_memory_budget(&mut self, bytes: usize) {
+        self.memory_budget = Some(bytes);
+    }
+
+    pub fn has_budget(&self) -> bool {
+        self.node_budget.is_some() || self.memory_budget.is_some()
+    }
+
+    /// Records that the data of the current node allocates `bytes` itself.
+    pub fn set_heap_size(&mut self, bytes: usize) {
+        let node = self.node_mut(self.current);
+        let before = std::mem::replace(&mut node.heap_size, bytes);
+        self.heap_size = self.heap_size - before + bytes;
+    }
+
+    /// Memory the budget is checked against: the nodes and the heap sizes of their data.
+    pub fn memory_used(&self) -> usize {
+        self.len() * Self::NODE_SIZE + self.heap_size
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len() - self.free_slots.len()
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.is_filled_to(1, 1)
+    }
+
+    /// Whether the tree takes at least `numerator` / `denominator` of one of its budgets.
+    pub fn is_filled_to(&self, numerator: usize, denominator: usize) -> bool {
+        self.node_budget
+            .is_some_and(|budget| self.len() * denominator >= budget * numerator)
+            || self
+                .memory_budget
+                .is_some_and(|budget| self.memory_used() * denominator >= budget * numerator)
+    }
+
+    /// Estimate of the memory taken by the tree.
+    pub fn memory_usage(&self) -> usize {
+        self.nodes.capacity() * size_of::<Option<Node<T>>>()
+            + self.free_slots.capacity() * size_of::<usize>()
+            + self.len() * size_of::<usize>()
+            + self.heap_size
+    }
+
+    pub fn root(&self) -> &Node<T> {
+        self.node(self.root)
+    }
+
+    pub fn root_mut(&mut self) -> &mut Node<T> {
+        self.node_mut(self.root)
+    }
+
+    pub fn current(&self) -> &Node<T> {
+        self.node(self.current)
+    }
+
+    pub fn current_mut(&mut self) -> &mut Node<T> {
+        self.node_mut(self.current)
+    }
+
+    /// Adds a child to the current node. Returns false, dropping the data, if the tree
+    /// has no budget left for it.
+    pub fn add_child(&mut self, data: T) -> bool {
+        if self.is_full() {
+            return false;
+        }
+        self.add_child_over_budget(data);
+        true
+    }
+
+    /// Adds a child to the current node even if that exceeds the budget of the tree.
+    pub fn add_child_over_budget(&mut self, data: T) {
+        let index = self.number_of_children();
+        let child = Some(Node::new_with_parent(data, Some(self.current), Some(index)));
+        let slot = match self.free_slots.pop() {
+            Some(slot) => {
+                self.nodes[slot] = child;
+                slot
+            }
+            None => {
+                self.nodes.push(child);
+                self.nodes.len() - 1
+            }
+        };
+        self.current_mut().children.push(slot);
     }
 
     pub fn has_no_child(&self) -> bool {
-        self.current.borrow().children.is_empty()
+        self.current().children.is_empty()
     }
 
     pub fn has_children(&self) -> bool {
@@ -62,37 +165,89 @@ impl<T> Tree<T> {
     }
 
     pub fn has_parent(&self) -> bool {
-        self.current.borrow().parent.is_some()
+        self.current().parent.is_some()
     }
 
     pub fn number_of_children(&self) -> usize {
-        self.current.borrow().children.len()
+        self.current().children.len()
     }
 
     pub fn goto_child(&mut self, i: usize) {
-        self.current = Rc::clone(&Rc::clone(&self.current).borrow().children[i]);
+        self.current = self.current().children[i];
     }
 
     pub fn goto_last_child(&mut self) {
-        let last_child_i = self.current.borrow().children.len() - 1;
-        self.current = Rc::clone(&Rc::clone(&self.current).borrow().children[last_child_i]);
+        self.current = *self.current().children.last().expect("no child to go to");
     }
 
     pub fn goto_parent(&mut self) {
-        match &Rc::clone(&self.current).borrow().parent {
-            Some(opt_reference) => match Weak::upgrade(opt_reference) {
-                Some(reference) => {
-                    self.current = reference;
-                }
-                None => {
-                    panic!("parent node was deleted before child")
-                }
-            },
-            None => {
-                panic!("no parent to go to")
-            }
+        self.current = self.current().parent.expect("no parent to go to");
+    }
+
+    pub fn goto_root(&mut self) {
+        self.current = self.root;
+    }
+
+    /// Index of the first child of the current node whose data matches.
+    pub fn find_child(&self, matches: impl Fn(&T) -> bool) -> Option<usize> {
+        self.current()
+            .children
+            .iter()
+            .position(|child| matches(&self.node(*child).data))
+    }
+
+    /// Calls `f` on the data of the current node and of all nodes below it.
+    pub fn for_each_in_subtree(&mut self, mut f: impl FnMut(&mut T)) {
+        let mut stack = vec![self.current];
+        while let Some(slot) = stack.pop() {
+            let node = self.node_mut(slot);
+            f(&mut node.data);
+            stack.extend(node.children.iter().copied());
+        }
+    }
+
+    /// Frees all subtrees of the current node at once.
+    pub fn remove_children(&mut self) {
+        let children = std::mem::take(&mut self.current_mut().children);
+        for child in children {
+            self.free_subtree(child);
+        }
+    }
+
+    /// Makes the current node the root, freeing everything that is not below it.
+    pub fn make_current_root(&mut self) {
+        let new_root = self.current;
+        if new_root == self.root {
+            return;
+        }
+        let parent = self.current().parent.unwrap();
+        let index = self.current().index.unwrap();
+        // Cut the new root loose, so that freeing the old root leaves it alone
+        self.node_mut(parent).children.remove(index);
+        self.free_subtree(self.root);
+        let node = self.current_mut();
+        node.parent = None;
+        node.index = None;
+        self.root = new_root;
+    }
+
+    fn free_subtree(&mut self, slot: usize) {
+        let mut stack = vec![slot];
+        while let Some(slot) = stack.pop() {
+            let node = self.nodes[slot].take().expect("node freed twice");
+            self.heap_size -= node.heap_size;
+            stack.extend(node.children);
+            self.free_slots.push(slot);
         }
     }
+
+    fn node(&self, slot: usize) -> &Node<T> {
+        self.nodes[slot].as_ref().expect("node was freed")
+    }
+
+    fn node_mut(&mut self, slot: usize) -> &mut Node<T> {
+        self.nodes[slot].as_mut().expect("node was freed")
+    }
 }
 
 #[cfg(test)]
@@ -103,31 +258,127 @@ mod tests {
     fn test_adding_children_and_going_back() {
         let mut tree = Tree::<u8>::new(0);
         for i in 1..5 {
-            let child_val = i + tree.current.borrow().data;
+            let child_val = i + tree.current().data;
             tree.add_child(i + child_val);
-            println!("data of current: {}", tree.current.borrow().data);
-            println!("parent of current: {:?}", tree.current.borrow().parent);
+            println!("data of current: {}", tree.current().data);
+            println!("parent of current: {:?}", tree.current().parent);
             tree.goto_child(0);
         }
         for _ in 1..5 {
-            println!("data of current: {}", tree.current.borrow().data);
+            println!("data of current: {}", tree.current().data);
             tree.goto_parent();
         }
+        assert!(!tree.has_parent());
+    }
+
+    #[test]
+    fn test_making_grandchild_the_root() {
+        let mut tree = Tree::<u8>::new(0);
+        tree.add_child(1);
+        tree.add_child(2);
+        tree.goto_child(1);
+        tree.add_child(3);
+        tree.add_child(4);
+        tree.goto_parent();
+
+        let child = tree.find_child(|data| *data == 2).unwrap();
+        tree.goto_child(child);
+        let grandchild = tree.find_child(|data| *data == 4).unwrap();
+        tree.goto_child(grandchild);
+        tree.add_child(5);
+        tree.make_current_root();
+
+        assert!(!tree.has_parent());
+        assert_eq!(tree.root().data, 4);
+        assert_eq!(tree.len(), 2);
+        let mut data = vec![];
+        tree.for_each_in_subtree(|value| data.push(*value));
+        assert_eq!(data, vec![4, 5]);
     }
 
     #[test]
     fn test_adding_children_and_going_back_2() {
         let mut tree = Tree::<u8>::new(0);
         for i in 1..5 {
-            let child_val = i + tree.current.borrow().data;
+            let child_val = i + tree.current().data;
             tree.add_child(i + child_val);
-            println!("data of current: {}", tree.current.borrow().data);
-            println!("parent of current: {:?}", tree.current.borrow().parent);
+            println!("data of current: {}", tree.current().data);
+            println!("parent of current: {:?}", tree.current().parent);
             tree.goto_last_child();
         }
         for _ in 1..5 {
-            println!("data of current: {}", tree.current.borrow().data);
+            println!("data of current: {}", tree.current().data);
             tree.goto_parent();
         }
     }
+
+    #[test]
+    fn test_freed_slots_are_reused() {
+        let mut tree = Tree::<u8>::new(0);
+        for i in 1..4 {
+            tree.add_child(i);
+            tree.goto_last_child();
+            tree.add_child(i);
+            tree.goto_parent();
+        }
+        assert_eq!(tree.len(), 7);
+        tree.remove_children();
+        assert_eq!(tree.len(), 1);
+        assert!(tree.has_no_child());
+
+        let slots = tree.nodes.len();
+        for i in 1..7 {
+            tree.add_child(i);
+        }
+        assert_eq!(tree.nodes.len(), slots);
+        assert_eq!(tree.len(), 7);
+    }
+
+    #[test]
+    fn test_node_budget() {
+        let mut tree = Tree::<u8>::new(0);
+        tree.set_node_budget(Some(3));
+        assert!(tree.add_child(1));
+        assert!(tree.add_child(2));
+        assert!(tree.is_full());
+        assert!(!tree.add_child(3));
+        assert_eq!(tree.number_of_children(), 2);
+
+        tree.set_node_budget(None);
+        tree.set_memory_budget(10 * Tree::<u8>::NODE_SIZE);
+        assert!(tree.add_child(3));
+        assert!(tree.memory_usage() >= tree.len() * size_of::<Node<u8>>());
+    }
+
+    #[test]
+    fn test_memory_budget_counts_heap_sizes() {
+        let mut tree = Tree::<u8>::new(0);
+        tree.set_memory_budget(10 * Tree::<u8>::NODE_SIZE);
+        tree.add_child(1);
+        tree.goto_last_child();
+        tree.set_heap_size(7 * Tree::<u8>::NODE_SIZE);
+        assert_eq!(tree.memory_used(), 9 * Tree::<u8>::NODE_SIZE);
+        assert!(tree.add_child(2));
+        assert!(tree.is_full());
+        assert!(!tree.add_child(3));
+
+        // Freeing a node gives its heap size back
+        tree.goto_root();
+        tree.remove_children();
+        assert_eq!(tree.memory_used(), Tree::<u8>::NODE_SIZE);
+        assert!(!tree.is_filled_to(1, 2));
+    }
+
+    #[test]
+    fn test_tree_can_be_sent_to_another_thread() {
+        let mut tree = Tree::<u8>::new(0);
+        tree.add_child(1);
+        let tree = std::thread::spawn(move || {
+            tree.goto_child(0);
+            tree
+        })
+        .join()
+        .unwrap();
+        assert_eq!(tree.current().data, 1);
+    }
 }
@@ -1,13 +1,15 @@
-use chess::{Board, BoardBuilder, CastleRights, Color, File, Piece, Rank, Square};
+use chess::{Board, Color, Piece, Square};
+#[cfg(test)]
+use chess::{BoardBuilder, CastleRights, File, Rank};
 use std::string::String as StdString;
+#[cfg(test)]
 use string::String;
 
+#[cfg(not(test))]
+use log::info;
 
-#[cfg(not(test))] 
-use log::{info, warn};
- 
 #[cfg(test)]
-use std::{println as info, println as warn};
+use std::println as info;
 
 
 pub fn show_board(board: Board) {
@@ -29,6 +31,7 @@ pub fn show_board(board: Board) {
 }
 
 
+#[cfg(test)]
 pub fn board_from_textboard(
     textboard: &str,
     white_castle_rights: CastleRights,
@@ -37,23 +40,20 @@ pub fn board_from_textboard(
 ) -> Board {
     let mut position = BoardBuilder::new();
     let lines = textboard_lines(textboard);
-    for i in 0..8 {
+    for (i, line) in lines.iter().enumerate().take(8) {
         let rank = 7 - i;
-        let pieces: Vec<Option<(Piece, Color)>> = lines[i]
-            .split("|")
+        let pieces: Vec<Option<(Piece, Color)>> = line
+            .split('|')
             .filter(|field| field.len() > 1)
             .map(|line| char_to_piece(line.trim()))
             .collect();
         for (file, piece_color) in pieces.iter().enumerate() {
-            match piece_color {
-                Some((piece, color)) => {
-                    position.piece(
-                        Square::make_square(Rank::from_index(rank), File::from_index(file)),
-                        *piece,
-                        *color,
-                    );
-                }
-                None => {}
+            if let Some((piece, color)) = piece_color {
+                position.piece(
+                    Square::make_square(Rank::from_index(rank), File::from_index(file)),
+                    *piece,
+                    *color,
+                );
             }
         }
     }
@@ -64,12 +64,13 @@ pub fn board_from_textboard(
     Board::try_from(position).unwrap()
 }
 
+#[cfg(test)]
 fn textboard_lines(textboard: &str) -> Vec<String> {
     let text: String = String::from_str(textboard);
     text.split("\n")
         .map(|line| line.trim())
         .filter(|line| !line.is_empty())
-        .map(|line| String::from_str(line))
+        .map(String::from_str)
         .collect()
 }
 
@@ -90,6 +91,7 @@ fn piece_to_char(piece: Piece, color: Color) -> StdString {
     }
 }
 
+#[cfg(test)]
 fn char_to_piece(char: &str) -> Option<(Piece, Color)> {
     match char {
         "♚" => Some((Piece::King, Color::White)),
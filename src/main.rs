@@ -8,17 +8,18 @@ use std::sync::Arc;
 use std::thread;
 use std::time::Duration;
 use std::env;
-use vampirc_uci::parse_one;
 
-use engine::Engine;
+use engine::search::MAX_DEPTH;
+use engine::{parse_command, Engine};
 
+mod bench;
 mod engine;
 
-#[cfg(not(test))] 
-use log::{info, warn};
- 
+#[cfg(not(test))]
+use log::info;
+
 #[cfg(test)]
-use std::{println as info, println as warn};
+use std::println as info;
 
 fn main() {
     let path = Path::new("engine.log");
@@ -27,19 +28,54 @@ fn main() {
 
     let mut input = String::new();
     let running = Arc::new(AtomicBool::new(true));
-    let mut depth = 3;
+    let mut depth = MAX_DEPTH;
     let args: Vec<String> = env::args().collect();
+    if args.len() >= 3 && args[1] == "bench" && args[2] == "tree" {
+        bench::tree();
+        return;
+    }
+    if args.len() >= 3 && args[1] == "bench" && args[2] == "depth" {
+        // Optional argument is the search time per position in milliseconds
+        let move_time = args.get(3).map_or(Ok(1000), |millis| millis.parse::<u64>());
+        bench::depth(Duration::from_millis(move_time.expect("bench time should be in milliseconds")));
+        return;
+    }
+    if args.len() >= 3 && args[1] == "bench" && args[2] == "probcut" {
+        // Optional argument is the search time per move in milliseconds
+        let move_time = args.get(3).map_or(Ok(150), |millis| millis.parse::<u64>());
+        bench::probcut(Duration::from_millis(move_time.expect("bench time should be in milliseconds")));
+        return;
+    }
+    if args.len() >= 2 && args[1] == "bench" {
+        // Optional arguments are the search time per position in milliseconds,
+        // and the depth to measure the time to reach
+        let move_time = args.get(2).map_or(Ok(1000), |millis| millis.parse::<u64>());
+        let depth = args.get(3).map_or(Ok(6), |depth| depth.parse::<u8>());
+        bench::run(
+            Duration::from_millis(move_time.expect("bench time should be in milliseconds")),
+            depth.expect("bench depth should be a number"),
+        );
+        return;
+    }
     if args.len() >= 2 {
         match args[1].parse::<u8>() {
             Ok(num) => {
                 depth = num;
             },
-            _ => {panic!("first argument if provided should be depth")}
+            _ => {panic!("first argument if provided should be maximum depth")}
         }
     }
+    let tree_memory = args.get(2).map(|size_mb| {
+        size_mb
+            .parse::<usize>()
+            .expect("second argument if provided should be the search tree memory in MB")
+    });
 
     let (handle, tx) = {
-        let engine = Engine::default();
+        let mut engine = Engine::default();
+        if let Some(size_mb) = tree_memory {
+            engine.set_tree_memory(size_mb);
+        }
         engine.start(depth)
     };
     let mut stdin = BufReader::new(stdin());
@@ -51,9 +87,9 @@ fn main() {
             if input.starts_with("quit") {
                 running.store(false, Ordering::Release);
             }
-            let message = parse_one(&input);
+            let command = parse_command(&input);
 
-            if tx.send(message).is_err() {
+            if tx.send(command).is_err() {
                 break;
             }
 